@@ -0,0 +1,376 @@
+//! Exact SOL and token amounts.
+//!
+//! Amounts are kept as integers in the smallest unit, lamports for SOL and base units for tokens,
+//! and converted from and to decimal strings without going through `f64`.
+//!
+//! `parse_amount` reads amounts typed by users, such as `"1,5"`, `"0.25 SOL"` or `"10 USDC"`, and
+//! `Lamports::format` and `TokenAmount::format` display them following a `NumberFormat`.
+
+use crate::Error;
+use solana_program::pubkey::Pubkey;
+use std::fmt;
+use std::str::FromStr;
+
+/// Number of decimals of SOL: one SOL is 10^9 lamports.
+pub const SOL_DECIMALS: u8 = 9;
+
+/// An amount of SOL, in lamports.
+///
+/// `Display` and `FromStr` use SOL, e.g. `"1.5"` is 1_500_000_000 lamports.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Lamports(pub u64);
+
+impl Lamports {
+    pub const ZERO: Self = Self(0);
+
+    pub const fn new(lamports: u64) -> Self {
+        Self(lamports)
+    }
+
+    pub const fn lamports(self) -> u64 {
+        self.0
+    }
+
+    /// Parses a decimal amount of SOL, e.g. `"0.25"`. Fails on more than 9 significant decimals.
+    pub fn from_sol_str(sol: &str) -> Result<Self, Error> {
+        parse_decimal(sol, SOL_DECIMALS).map(Self)
+    }
+
+    /// Converts to SOL for display purposes only; use `Display` when the exact value matters.
+    pub fn to_sol_f64(self) -> f64 {
+        self.0 as f64 / 10f64.powi(SOL_DECIMALS as i32)
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        self.0.checked_add(other.0).map(Self)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.0.checked_sub(other.0).map(Self)
+    }
+
+    pub fn checked_mul(self, factor: u64) -> Option<Self> {
+        self.0.checked_mul(factor).map(Self)
+    }
+
+    pub fn saturating_sub(self, other: Self) -> Self {
+        Self(self.0.saturating_sub(other.0))
+    }
+
+    /// Formats the amount in SOL following `format`, without unit.
+    pub fn format(self, format: &NumberFormat) -> String {
+        format_number(self.0, SOL_DECIMALS, format)
+    }
+}
+
+impl fmt::Display for Lamports {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&format_decimal(self.0, SOL_DECIMALS))
+    }
+}
+
+impl FromStr for Lamports {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_sol_str(s)
+    }
+}
+
+impl From<u64> for Lamports {
+    fn from(lamports: u64) -> Self {
+        Self(lamports)
+    }
+}
+
+impl From<Lamports> for u64 {
+    fn from(lamports: Lamports) -> Self {
+        lamports.0
+    }
+}
+
+/// An amount of a token in its base units, with the number of decimals of its mint.
+///
+/// `Display` uses whole tokens, e.g. 1_500_000 base units of a 6 decimals mint is `"1.5"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TokenAmount {
+    pub amount: u64,
+    pub decimals: u8,
+}
+
+impl TokenAmount {
+    pub const fn new(amount: u64, decimals: u8) -> Self {
+        Self { amount, decimals }
+    }
+
+    /// Parses a decimal amount of tokens, e.g. `"10.5"`. Fails on more significant decimals than
+    /// `decimals`.
+    pub fn parse(tokens: &str, decimals: u8) -> Result<Self, Error> {
+        parse_decimal(tokens, decimals).map(|amount| Self::new(amount, decimals))
+    }
+
+    /// Returns `None` on overflow or if the amounts have different decimals.
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        if self.decimals != other.decimals {
+            return None;
+        }
+        self.amount.checked_add(other.amount).map(|amount| Self::new(amount, self.decimals))
+    }
+
+    /// Returns `None` on underflow or if the amounts have different decimals.
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        if self.decimals != other.decimals {
+            return None;
+        }
+        self.amount.checked_sub(other.amount).map(|amount| Self::new(amount, self.decimals))
+    }
+
+    /// Formats the amount in whole tokens following `format`, without unit.
+    pub fn format(self, format: &NumberFormat) -> String {
+        format_number(self.amount, self.decimals, format)
+    }
+}
+
+impl fmt::Display for TokenAmount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&format_decimal(self.amount, self.decimals))
+    }
+}
+
+impl From<Lamports> for TokenAmount {
+    fn from(lamports: Lamports) -> Self {
+        Self::new(lamports.0, SOL_DECIMALS)
+    }
+}
+
+/// Separators and precision of displayed numbers. The default matches `Display`: `.` as decimal
+/// separator, no grouping and no trailing zeros.
+///
+/// Both separators must differ for numbers to be read back, which `ParseConfig::new` checks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumberFormat {
+    pub decimal_separator: char,
+    /// Separator between groups of three digits of the whole part, e.g. `,` in `"1,000.5"`.
+    pub group_separator: Option<char>,
+    /// Drops zeros at the end of the fractional part when formatting, e.g. `"1.5"` instead of
+    /// `"1.500000000"`. Has no effect on parsing.
+    pub trim_trailing_zeros: bool,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        Self {
+            decimal_separator: '.',
+            group_separator: None,
+            trim_trailing_zeros: true,
+        }
+    }
+}
+
+/// A mint users can refer to by symbol, e.g. `"10 USDC"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KnownMint {
+    pub symbol: String,
+    pub mint: Pubkey,
+    pub decimals: u8,
+}
+
+impl KnownMint {
+    pub fn new(symbol: &str, mint: Pubkey, decimals: u8) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            mint,
+            decimals,
+        }
+    }
+}
+
+/// Returns USDC and USDT on mainnet-beta. Other clusters use different mints.
+pub fn mainnet_mints() -> Vec<KnownMint> {
+    vec![
+        KnownMint::new("USDC", Pubkey::from_str("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v").unwrap(), 6),
+        KnownMint::new("USDT", Pubkey::from_str("Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB").unwrap(), 6),
+    ]
+}
+
+/// Settings of `parse_amount`, validated once by `new`. The default parses SOL amounts with the
+/// default `NumberFormat`.
+#[derive(Debug, Clone, Default)]
+pub struct ParseConfig {
+    format: NumberFormat,
+    known_mints: Vec<KnownMint>,
+    default_mint: Option<KnownMint>,
+}
+
+impl ParseConfig {
+    /// `format` has the separators the user types; group separators must separate groups of
+    /// exactly three digits. `known_mints` are accepted as unit, matched by symbol regardless of
+    /// case. `default_mint` is the mint of amounts typed without unit, `None` for SOL.
+    ///
+    /// Fails with `Error::BadParameter` if both separators of `format` are the same, or if
+    /// `default_mint` is not one of `known_mints`.
+    pub fn new(format: NumberFormat, known_mints: Vec<KnownMint>, default_mint: Option<Pubkey>) -> Result<Self, Error> {
+        if format.group_separator == Some(format.decimal_separator) {
+            return Err(Error::BadParameter(format!(
+                "decimal and group separators are both {:?}",
+                format.decimal_separator
+            )));
+        }
+        let default_mint = match default_mint {
+            Some(mint) => Some(
+                known_mints
+                    .iter()
+                    .find(|known| known.mint == mint)
+                    .cloned()
+                    .ok_or_else(|| Error::BadParameter("default_mint is not one of known_mints".to_string()))?,
+            ),
+            None => None,
+        };
+
+        Ok(Self {
+            format,
+            known_mints,
+            default_mint,
+        })
+    }
+
+    pub fn format(&self) -> &NumberFormat {
+        &self.format
+    }
+
+    pub fn known_mints(&self) -> &[KnownMint] {
+        &self.known_mints
+    }
+
+    pub fn default_mint(&self) -> Option<&KnownMint> {
+        self.default_mint.as_ref()
+    }
+}
+
+/// An amount parsed by `parse_amount`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Amount {
+    Sol(Lamports),
+    Token { mint: Pubkey, amount: TokenAmount },
+}
+
+/// Parses an amount typed by a user, made of a number and an optional unit: `SOL`, `lamports`
+/// or the symbol of one of `config.known_mints`, e.g. `"1.5"`, `"0.25 SOL"`, `"2500000 lamports"`
+/// or `"10 USDC"`.
+///
+/// Amounts more precise than their unit, such as `"0.5 lamports"`, are rejected rather than
+/// truncated.
+pub fn parse_amount(s: &str, config: &ParseConfig) -> Result<Amount, Error> {
+    let s = s.trim();
+    let (number, unit) = match s.find(char::is_alphabetic) {
+        Some(index) => (s[..index].trim_end(), Some(&s[index..])),
+        None => (s, None),
+    };
+    let unknown_unit = || Error::InvalidAmount(format!("{:?} has an unknown unit", s));
+
+    let (decimals, mint) = match unit {
+        None => match &config.default_mint {
+            None => (SOL_DECIMALS, None),
+            Some(known) => (known.decimals, Some(known.mint)),
+        },
+        Some(unit) if unit.eq_ignore_ascii_case("sol") => (SOL_DECIMALS, None),
+        Some(unit) if unit.eq_ignore_ascii_case("lamport") || unit.eq_ignore_ascii_case("lamports") => (0, None),
+        Some(unit) => {
+            let known = config
+                .known_mints
+                .iter()
+                .find(|known| known.symbol.eq_ignore_ascii_case(unit))
+                .ok_or_else(unknown_unit)?;
+            (known.decimals, Some(known.mint))
+        }
+    };
+
+    let amount = parse_number(number, decimals, &config.format)?;
+    Ok(match mint {
+        None => Amount::Sol(Lamports(amount)),
+        Some(mint) => Amount::Token {
+            mint,
+            amount: TokenAmount::new(amount, decimals),
+        },
+    })
+}
+
+/// Parses a non-negative decimal number into base units with `decimals` decimals. Trailing zeros
+/// beyond `decimals` are accepted, any other excess precision is an error rather than truncated.
+pub(crate) fn parse_decimal(s: &str, decimals: u8) -> Result<u64, Error> {
+    parse_number(s, decimals, &NumberFormat::default())
+}
+
+/// Formats base units as a decimal number with `decimals` decimals, without trailing zeros.
+pub(crate) fn format_decimal(amount: u64, decimals: u8) -> String {
+    format_number(amount, decimals, &NumberFormat::default())
+}
+
+/// Like `parse_decimal`, with the separators of `format`.
+fn parse_number(s: &str, decimals: u8, format: &NumberFormat) -> Result<u64, Error> {
+    let invalid = |reason: &str| Error::InvalidAmount(format!("{:?} {}", s, reason));
+
+    let (whole, fraction) = match s.split_once(format.decimal_separator) {
+        Some((whole, fraction)) => (whole, fraction),
+        None => (s, ""),
+    };
+    let whole = match format.group_separator {
+        Some(separator) if whole.contains(separator) => {
+            let mut groups = whole.split(separator);
+            let first = groups.next().unwrap_or_default();
+            if first.is_empty() || first.len() > 3 || groups.any(|group| group.len() != 3) {
+                return Err(invalid("has misplaced digit group separators"));
+            }
+            whole.replace(separator, "")
+        }
+        _ => whole.to_string(),
+    };
+    if whole.is_empty() && fraction.is_empty() {
+        return Err(invalid("is not a number"));
+    }
+    if !whole.bytes().chain(fraction.bytes()).all(|b| b.is_ascii_digit()) {
+        return Err(invalid("is not a number"));
+    }
+
+    let decimals = decimals as usize;
+    let significant = fraction.trim_end_matches('0');
+    if significant.len() > decimals {
+        return Err(invalid(&format!("has more than {} decimals", decimals)));
+    }
+
+    let digits = format!("{}{}{}", whole, significant, "0".repeat(decimals - significant.len()));
+    let digits = digits.trim_start_matches('0');
+    if digits.is_empty() {
+        return Ok(0);
+    }
+
+    digits.parse().map_err(|_| invalid("is too large"))
+}
+
+/// Like `format_decimal`, following `format`.
+fn format_number(amount: u64, decimals: u8, format: &NumberFormat) -> String {
+    let decimals = decimals as usize;
+    let digits = format!("{:0>width$}", amount, width = decimals + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - decimals);
+    let fraction = if format.trim_trailing_zeros {
+        fraction.trim_end_matches('0')
+    } else {
+        fraction
+    };
+
+    let mut formatted = String::with_capacity(digits.len() + digits.len() / 3 + 1);
+    for (i, digit) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+            if let Some(separator) = format.group_separator {
+                formatted.push(separator);
+            }
+        }
+        formatted.push(digit);
+    }
+    if !fraction.is_empty() {
+        formatted.push(format.decimal_separator);
+        formatted.push_str(fraction);
+    }
+
+    formatted
+}
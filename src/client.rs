@@ -0,0 +1,265 @@
+use crate::history::HistoryEntry;
+use crate::{
+    check_memo_fits, fetch_signatures, fetch_transactions, history_commitment, offline, prepare_transfer,
+    process_transaction, resolve_blockhash, sign_and_process_transaction, Error, Lamports,
+    PreparedTransaction, SpendAmount, TransactionConfig, TransferTemplate,
+};
+use solana_client::rpc_client::RpcClient;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    hash::Hash,
+    message::Message,
+    signature::{Keypair, Signature, Signer},
+    system_instruction,
+};
+use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// The Solana cluster a `PayClient` talks to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cluster {
+    MainnetBeta,
+    Testnet,
+    Devnet,
+    Localnet,
+    /// Any other RPC endpoint, e.g. a private mainnet provider.
+    Custom(String),
+}
+
+impl Cluster {
+    /// Returns the JSON RPC URL of the cluster.
+    pub fn url(&self) -> &str {
+        match self {
+            Cluster::MainnetBeta => "https://api.mainnet-beta.solana.com",
+            Cluster::Testnet => "https://api.testnet.solana.com",
+            Cluster::Devnet => "https://api.devnet.solana.com",
+            Cluster::Localnet => "http://localhost:8899",
+            Cluster::Custom(url) => url,
+        }
+    }
+}
+
+/// Settings applied to every request made by a `PayClient`.
+#[derive(Debug, Clone)]
+pub struct PayClientConfig {
+    /// Commitment used for balance checks, blockhashes and confirmations.
+    pub commitment: CommitmentConfig,
+    /// Timeout of a single RPC request.
+    pub timeout: Duration,
+    /// How many requests are made in parallel when loading history or discovering accounts.
+    pub max_concurrent_requests: usize,
+}
+
+impl Default for PayClientConfig {
+    fn default() -> Self {
+        Self {
+            commitment: CommitmentConfig::finalized(),
+            timeout: Duration::from_secs(30),
+            max_concurrent_requests: 8,
+        }
+    }
+}
+
+/// A connection to a Solana cluster that can be reused across calls.
+///
+/// The free functions of this crate create a new `PayClient` on every call; prefer keeping one
+/// around so the underlying HTTP connections are reused.
+pub struct PayClient {
+    rpc_client: RpcClient,
+    cluster: Cluster,
+    config: PayClientConfig,
+}
+
+impl PayClient {
+    pub fn new(cluster: Cluster) -> Self {
+        Self::new_with_config(cluster, PayClientConfig::default())
+    }
+
+    pub fn new_with_config(cluster: Cluster, config: PayClientConfig) -> Self {
+        let rpc_client = RpcClient::new_with_timeout_and_commitment(
+            cluster.url().to_string(),
+            config.timeout,
+            config.commitment,
+        );
+
+        Self {
+            rpc_client,
+            cluster,
+            config,
+        }
+    }
+
+    /// Returns the underlying RPC client for requests not covered by `PayClient`.
+    pub fn rpc_client(&self) -> &RpcClient {
+        &self.rpc_client
+    }
+
+    pub fn cluster(&self) -> &Cluster {
+        &self.cluster
+    }
+
+    pub fn commitment(&self) -> CommitmentConfig {
+        self.config.commitment
+    }
+
+    /// Returns the SOL balance of the given wallet address.
+    pub fn get_balance(&self, base58_pubkey: &str) -> Result<Lamports, Error> {
+        let pubkey = Pubkey::from_str(base58_pubkey)?;
+        let balance = self
+            .rpc_client
+            .get_balance_with_commitment(&pubkey, self.commitment())?
+            .value;
+
+        Ok(Lamports(balance))
+    }
+
+    /// Returns the history of each transaction in order from latest to earliest.
+    pub fn process_transaction_history(
+        &self,
+        address: &Pubkey,
+        before: Option<Signature>,
+        until: Option<Signature>,
+        limit: usize,
+    ) -> Result<Vec<EncodedConfirmedTransactionWithStatusMeta>, Error> {
+        self.get_transaction_history(address, before, until, limit)?
+            .into_iter()
+            .map(|entry| entry.transaction)
+            .collect()
+    }
+
+    /// Like `process_transaction_history`, but a transaction that fails to load is reported in its
+    /// own entry instead of failing the whole page. Transactions are fetched concurrently, see
+    /// `PayClientConfig::max_concurrent_requests`.
+    pub fn get_transaction_history(
+        &self,
+        address: &Pubkey,
+        before: Option<Signature>,
+        until: Option<Signature>,
+        limit: usize,
+    ) -> Result<Vec<HistoryEntry>, Error> {
+        let signatures = fetch_signatures(&self.rpc_client, self.history_commitment(), address, before, until, limit)?;
+        let transactions = self.fetch_transactions(&signatures);
+
+        Ok(signatures
+            .into_iter()
+            .zip(transactions)
+            .map(|(signature, transaction)| HistoryEntry {
+                signature,
+                transaction,
+            })
+            .collect())
+    }
+
+    pub(crate) fn fetch_transactions(&self, signatures: &[Signature]) -> Vec<Result<EncodedConfirmedTransactionWithStatusMeta, Error>> {
+        fetch_transactions(&self.rpc_client, self.history_commitment(), signatures, self.config.max_concurrent_requests)
+    }
+
+    pub(crate) fn max_concurrent_requests(&self) -> usize {
+        self.config.max_concurrent_requests
+    }
+
+    pub(crate) fn history_commitment(&self) -> CommitmentConfig {
+        history_commitment(self.commitment())
+    }
+
+    /// Prepares a transaction to send `amount` SOL to `recipient`'s wallet address. The
+    /// transaction is initiated from `sender`'s address. The transaction can later be finished by
+    /// `finish_transaction`.
+    pub fn create_transaction(&self, sender: &Pubkey, amount: Lamports, recipient: &Pubkey) -> Result<PreparedTransaction, Error> {
+        let amount = SpendAmount::Some(amount);
+
+        self.create_transaction_with_config(sender, amount, recipient, &TransactionConfig::default())
+    }
+
+    /// Like `create_transaction`, but uses `blockhash` instead of fetching the latest one. The
+    /// blockhash must still be valid on the cluster.
+    ///
+    /// This is the first step of the air-gapped flow described in `offline`.
+    pub fn create_transaction_with_blockhash(&self, sender: &Pubkey, amount: Lamports, recipient: &Pubkey, blockhash: Hash) -> Result<PreparedTransaction, Error> {
+        let amount = SpendAmount::Some(amount);
+        let config = TransactionConfig {
+            blockhash: Some(blockhash),
+            ..TransactionConfig::default()
+        };
+
+        self.create_transaction_with_config(sender, amount, recipient, &config)
+    }
+
+    /// Like `create_transaction`, with the optional settings in `config`. `amount` can also ask for
+    /// the whole balance; `PreparedTransaction::amount` then reports what will be transferred.
+    pub fn create_transaction_with_config(&self, sender: &Pubkey, amount: SpendAmount, recipient: &Pubkey, config: &TransactionConfig) -> Result<PreparedTransaction, Error> {
+        let fee_payer = config.fee_payer.as_ref().unwrap_or(sender);
+        let nonce_authority = config.nonce_authority.as_ref().unwrap_or(sender);
+
+        check_memo_fits(vec![system_instruction::transfer(sender, recipient, 0)], fee_payer, nonce_authority, config)?;
+        if config.refuse_risky_recipients {
+            self.check_recipient(sender, recipient)?;
+        }
+
+        let (recent_blockhash, last_valid_block_height) = resolve_blockhash(&self.rpc_client, self.commitment(), config.blockhash, config.nonce_account.as_ref())?;
+        let compute_budget_ixs = config.priority_fee.instructions(&self.rpc_client, &[*fee_payer, *sender, *recipient])?;
+        let template = TransferTemplate {
+            sender,
+            to: recipient,
+            compute_budget_ixs: &compute_budget_ixs,
+            fee_payer,
+            nonce_account: config.nonce_account.as_ref(),
+            nonce_authority,
+            memo: config.memo.as_ref(),
+            recent_blockhash: &recent_blockhash,
+        };
+        let (message, cost) = prepare_transfer(&self.rpc_client, self.commitment(), amount, &template)?;
+
+        Ok(PreparedTransaction {
+            message,
+            fee: Lamports(cost.fee),
+            amount: Lamports(cost.spend).into(),
+            last_valid_block_height,
+        })
+    }
+
+    /// Signs and executes a transaction previously created by `create_transaction`.
+    /// Returns the base58 signature of the transaction as soon as the cluster accepted it; use
+    /// `wait_for_confirmation` to learn whether it landed.
+    pub fn finish_transaction(&self, private_key: &Keypair, message: Message) -> Result<String, Error> {
+        self.sign_and_send(private_key, None, None, message)
+    }
+
+    /// Like `finish_transaction`, for a transaction prepared against `nonce_account`. Fails if the
+    /// nonce has been advanced since, or if `private_key` is not the nonce authority.
+    pub fn finish_transaction_with_nonce(&self, private_key: &Keypair, message: Message, nonce_account: &Pubkey) -> Result<String, Error> {
+        self.sign_and_send(private_key, None, Some(nonce_account), message)
+    }
+
+    /// Like `finish_transaction_with_nonce`, for a transaction prepared with
+    /// `TransactionConfig::nonce_authority`. Both keypairs sign.
+    pub fn finish_transaction_with_nonce_authority(&self, private_key: &Keypair, nonce_authority: &Keypair, message: Message, nonce_account: &Pubkey) -> Result<String, Error> {
+        self.sign_and_send(private_key, Some(nonce_authority), Some(nonce_account), message)
+    }
+
+    fn sign_and_send(&self, private_key: &Keypair, nonce_authority: Option<&Keypair>, nonce_account: Option<&Pubkey>, message: Message) -> Result<String, Error> {
+        let no_wait = true;
+        let nonce_authority = nonce_authority.map(|nonce_authority| nonce_authority as &dyn Signer);
+
+        sign_and_process_transaction(&self.rpc_client, self.commitment(), private_key, nonce_authority, no_wait, nonce_account, message)
+    }
+
+    /// Broadcasts a transaction in the `offline` exchange format once every required signature has
+    /// been added.
+    pub fn send_signed_transaction(&self, encoded: &str) -> Result<Signature, Error> {
+        let transaction = offline::decode_transaction(encoded)?;
+
+        if !transaction.is_signed() {
+            return Err(Error::InvalidTransaction("transaction is missing signatures".to_string()));
+        }
+        transaction
+            .verify()
+            .map_err(|e| Error::InvalidTransaction(e.to_string()))?;
+
+        let no_wait = true;
+
+        process_transaction(&self.rpc_client, &transaction, no_wait)
+    }
+}
@@ -0,0 +1,172 @@
+//! Priority fees paid through the compute budget program.
+//!
+//! A transaction can ask for a compute unit limit and offer a price per compute unit in
+//! micro-lamports. Leaders favor transactions paying more, which helps payments land during
+//! congestion. The priority fee is `limit * price / 1_000_000` lamports, rounded up, on top of the
+//! base fee.
+//!
+//! Instructions are encoded by hand, see `token` for why.
+
+use crate::Error;
+use solana_client::{rpc_client::RpcClient, rpc_request::RpcRequest};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{compute_budget, instruction::Instruction, message::Message};
+use std::convert::{TryFrom, TryInto};
+
+/// Instruction tag of `ComputeBudgetInstruction::SetComputeUnitLimit`.
+const SET_COMPUTE_UNIT_LIMIT: u8 = 2;
+/// Instruction tag of `ComputeBudgetInstruction::SetComputeUnitPrice`.
+const SET_COMPUTE_UNIT_PRICE: u8 = 3;
+const MICRO_LAMPORTS_PER_LAMPORT: u128 = 1_000_000;
+
+/// How much to pay on top of the base fee, set with `TransactionConfig::priority_fee`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PriorityFee {
+    /// No compute budget instructions are added.
+    #[default]
+    None,
+    /// Pays `micro_lamports` per compute unit for up to `compute_unit_limit` units.
+    Fixed { compute_unit_limit: u32, micro_lamports: u64 },
+    /// Pays the given percentile (0 to 100) of the prices recently paid by transactions writing to
+    /// the same accounts, as reported by `getRecentPrioritizationFees`.
+    Percentile { compute_unit_limit: u32, percentile: u8 },
+}
+
+impl PriorityFee {
+    /// Returns the compute budget instructions to prepend to a transaction writing to
+    /// `writable_accounts`.
+    pub fn instructions(&self, rpc_client: &RpcClient, writable_accounts: &[Pubkey]) -> Result<Vec<Instruction>, Error> {
+        let fees_response: Option<serde_json::Value> = match self.fees_request(writable_accounts) {
+            Some((request, params)) => Some(rpc_client.send(request, params)?),
+            None => None,
+        };
+
+        self.instructions_from(fees_response.as_ref())
+    }
+
+    /// Returns the `getRecentPrioritizationFees` request `instructions` makes, if the fee depends
+    /// on recent fees. The RPC client at the pinned revision predates the method, so it is sent as
+    /// a custom request.
+    pub(crate) fn fees_request(&self, writable_accounts: &[Pubkey]) -> Option<(RpcRequest, serde_json::Value)> {
+        match self {
+            PriorityFee::Percentile { .. } => {
+                let accounts: Vec<String> = writable_accounts.iter().map(|account| account.to_string()).collect();
+                let request = RpcRequest::Custom {
+                    method: "getRecentPrioritizationFees",
+                };
+                Some((request, serde_json::json!([accounts])))
+            }
+            _ => None,
+        }
+    }
+
+    /// Builds the instructions from the response to `fees_request`, if it returned one.
+    pub(crate) fn instructions_from(&self, fees_response: Option<&serde_json::Value>) -> Result<Vec<Instruction>, Error> {
+        match *self {
+            PriorityFee::None => Ok(vec![]),
+            PriorityFee::Fixed {
+                compute_unit_limit,
+                micro_lamports,
+            } => Ok(compute_budget_instructions(compute_unit_limit, micro_lamports)),
+            PriorityFee::Percentile {
+                compute_unit_limit,
+                percentile,
+            } => {
+                let fees = parse_prioritization_fees(fees_response.unwrap_or(&serde_json::Value::Null))?;
+                Ok(compute_budget_instructions(compute_unit_limit, fee_percentile(fees, percentile)?))
+            }
+        }
+    }
+
+    /// Returns instructions of the same size as `instructions`, without making any request.
+    pub(crate) fn placeholder_instructions(&self) -> Vec<Instruction> {
+        match self {
+            PriorityFee::None => vec![],
+            _ => compute_budget_instructions(0, 0),
+        }
+    }
+}
+
+/// Returns the instructions setting the compute unit limit and price.
+pub fn compute_budget_instructions(compute_unit_limit: u32, micro_lamports: u64) -> Vec<Instruction> {
+    let mut limit_data = vec![SET_COMPUTE_UNIT_LIMIT];
+    limit_data.extend_from_slice(&compute_unit_limit.to_le_bytes());
+    let mut price_data = vec![SET_COMPUTE_UNIT_PRICE];
+    price_data.extend_from_slice(&micro_lamports.to_le_bytes());
+
+    vec![
+        Instruction::new_with_bytes(compute_budget::id(), &limit_data, vec![]),
+        Instruction::new_with_bytes(compute_budget::id(), &price_data, vec![]),
+    ]
+}
+
+/// Returns the priority fee in lamports for the given limit and price, saturating at `u64::MAX`
+/// for prices no one could pay.
+pub fn priority_fee_lamports(compute_unit_limit: u32, micro_lamports: u64) -> u64 {
+    let micro_lamports = compute_unit_limit as u128 * micro_lamports as u128;
+    let lamports = micro_lamports.div_ceil(MICRO_LAMPORTS_PER_LAMPORT);
+
+    u64::try_from(lamports).unwrap_or(u64::MAX)
+}
+
+/// Splits `message` into a copy without compute budget instructions and the priority fee they
+/// set. Clusters differ in whether `getFeeForMessage` includes the priority fee, so it is asked for
+/// the base fee only and the priority fee is added locally.
+pub(crate) fn split_priority_fee(message: &Message) -> (Message, u64) {
+    let mut compute_unit_limit = 0;
+    let mut micro_lamports = 0;
+    let mut base_message = message.clone();
+
+    base_message.instructions.retain(|ix| {
+        let program_id = message.account_keys.get(ix.program_id_index as usize);
+        if !program_id.is_some_and(compute_budget::check_id) {
+            return true;
+        }
+        match ix.data.split_first() {
+            Some((&SET_COMPUTE_UNIT_LIMIT, data)) => {
+                compute_unit_limit = data.try_into().map(u32::from_le_bytes).unwrap_or_default();
+            }
+            Some((&SET_COMPUTE_UNIT_PRICE, data)) => {
+                micro_lamports = data.try_into().map(u64::from_le_bytes).unwrap_or_default();
+            }
+            _ => {}
+        }
+        false
+    });
+
+    (base_message, priority_fee_lamports(compute_unit_limit, micro_lamports))
+}
+
+/// Returns the fee of `message` in lamports, including its priority fee.
+pub(crate) fn get_fee_for_message(rpc_client: &RpcClient, message: &Message) -> Result<u64, Error> {
+    let (base_message, priority_fee) = split_priority_fee(message);
+
+    Ok(rpc_client.get_fee_for_message(&base_message)?.saturating_add(priority_fee))
+}
+
+fn parse_prioritization_fees(response: &serde_json::Value) -> Result<Vec<u64>, Error> {
+    let unexpected = || Error::RpcRequestError("Unexpected getRecentPrioritizationFees response".to_string());
+
+    response
+        .as_array()
+        .ok_or_else(unexpected)?
+        .iter()
+        .map(|entry| entry["prioritizationFee"].as_u64().ok_or_else(unexpected))
+        .collect()
+}
+
+/// Returns the `percentile` of `fees` using the nearest-rank method, or 0 when there are none.
+fn fee_percentile(mut fees: Vec<u64>, percentile: u8) -> Result<u64, Error> {
+    if percentile > 100 {
+        return Err(Error::BadParameter(format!("percentile must be at most 100, got {}", percentile)));
+    }
+    if fees.is_empty() {
+        return Ok(0);
+    }
+
+    fees.sort_unstable();
+    let rank = (fees.len() * percentile as usize).div_ceil(100);
+
+    Ok(fees[rank.saturating_sub(1)])
+}
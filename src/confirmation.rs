@@ -0,0 +1,296 @@
+//! Tracking a sent transaction until it reaches a commitment level.
+//!
+//! `finish_transaction` returns as soon as the cluster accepted the transaction. Pass the returned
+//! signature to `PayClient::wait_for_confirmation` to learn whether the payment actually landed.
+//!
+//! Under congestion, accepted transactions are regularly dropped before reaching a leader.
+//! `PayClient::send_with_rebroadcast` keeps sending the same signed transaction until it lands or
+//! can no longer land, and tells which one happened.
+
+use crate::{get_latest_blockhash, is_blockhash_valid, Error, PayClient};
+use solana_client::{nonce_utils, rpc_config::RpcSendTransactionConfig};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{
+    clock::Slot,
+    commitment_config::CommitmentConfig,
+    hash::Hash,
+    message::Message,
+    signature::{Keypair, Signature},
+    transaction::{uses_durable_nonce, Transaction, TransactionError},
+};
+use solana_transaction_status::{TransactionConfirmationStatus, TransactionStatus};
+use std::time::{Duration, Instant};
+
+/// Delay between two status requests.
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// How long `PayClient::send_with_rebroadcast` keeps polling a transaction that expired after the
+/// cluster processed it, for it to reach the requested commitment.
+const EXPIRY_GRACE_PERIOD: Duration = Duration::from_secs(30);
+/// How many times in a row a request of `PayClient::send_with_rebroadcast` may fail before its
+/// error is returned.
+const MAX_CONSECUTIVE_FAILURES: u32 = 10;
+
+/// Progress reported on every poll until the requested commitment is reached.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfirmationProgress {
+    /// The cluster hasn't seen the transaction yet.
+    NotFound,
+    /// The transaction was processed in `slot` but hasn't reached the requested commitment.
+    Pending {
+        slot: Slot,
+        confirmation_status: Option<TransactionConfirmationStatus>,
+        /// Number of blocks confirmed on top of `slot`.
+        confirmations: Option<usize>,
+    },
+}
+
+/// The final status of a transaction that reached the requested commitment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionConfirmation {
+    pub signature: Signature,
+    pub slot: Slot,
+    pub confirmation_status: Option<TransactionConfirmationStatus>,
+    /// The outcome of the transaction. Failed transactions moved nothing but still paid the fee.
+    pub status: Result<(), TransactionError>,
+}
+
+/// Settings of `PayClient::send_with_rebroadcast`.
+#[derive(Debug, Clone)]
+pub struct RebroadcastConfig {
+    /// Commitment the transaction must reach.
+    pub commitment: CommitmentConfig,
+    /// How often the transaction is sent again while the cluster hasn't seen it.
+    pub interval: Duration,
+    /// Last block height at which the transaction's blockhash is accepted, usually
+    /// `PreparedTransaction::last_valid_block_height`. When not set, it is looked up if the
+    /// blockhash is still the latest one, otherwise the blockhash itself is checked on every poll.
+    pub last_valid_block_height: Option<u64>,
+}
+
+impl Default for RebroadcastConfig {
+    fn default() -> Self {
+        Self {
+            commitment: CommitmentConfig::confirmed(),
+            interval: Duration::from_secs(2),
+            last_valid_block_height: None,
+        }
+    }
+}
+
+/// The result of `PayClient::send_with_rebroadcast`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SendOutcome {
+    /// The transaction reached the requested commitment. It may still have failed, see
+    /// `TransactionConfirmation::status`.
+    Confirmed(TransactionConfirmation),
+    /// The blockhash expired, or the durable nonce was advanced, without the transaction being
+    /// processed. It can never be processed anymore, so it is safe to sign a new one for the same
+    /// payment.
+    Expired(Signature),
+}
+
+/// Consecutive failures of one kind of request.
+#[derive(Default)]
+struct FailureCount(u32);
+
+impl FailureCount {
+    /// Returns the value of a successful request, `None` for a failed one that should be retried,
+    /// or its error once `MAX_CONSECUTIVE_FAILURES` requests failed in a row.
+    fn check<T, E: Into<Error>>(&mut self, result: Result<T, E>) -> Result<Option<T>, Error> {
+        match result {
+            Ok(value) => {
+                self.0 = 0;
+                Ok(Some(value))
+            }
+            Err(e) => {
+                self.0 += 1;
+                if self.0 >= MAX_CONSECUTIVE_FAILURES {
+                    Err(e.into())
+                } else {
+                    Ok(None)
+                }
+            }
+        }
+    }
+}
+
+/// What makes a transaction unprocessable.
+enum Expiry {
+    BlockHeight(u64),
+    Blockhash(Hash),
+    Nonce { account: Pubkey, nonce: Hash },
+}
+
+impl PayClient {
+    /// Polls the status of `signature` until it reaches `commitment`, calling `progress` after each
+    /// request that didn't. Failed requests are retried. Fails with `Error::ConfirmationTimeout`
+    /// once `timeout` has passed.
+    ///
+    /// A transaction that failed on chain is still confirmed; its error is returned in
+    /// `TransactionConfirmation::status`.
+    pub fn wait_for_confirmation<F>(&self, signature: &Signature, commitment: CommitmentConfig, timeout: Duration, mut progress: F) -> Result<TransactionConfirmation, Error>
+    where
+        F: FnMut(&ConfirmationProgress),
+    {
+        let start = Instant::now();
+
+        loop {
+            // Transient RPC errors are retried until the timeout.
+            if let Ok(response) = self.rpc_client().get_signature_statuses(&[*signature]) {
+                let status = response.value.into_iter().next().flatten();
+                match check_confirmation(signature, status, commitment) {
+                    Ok(confirmation) => return Ok(confirmation),
+                    Err(pending) => progress(&pending),
+                }
+            }
+
+            if start.elapsed() >= timeout {
+                return Err(Error::ConfirmationTimeout(*signature));
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Signs a transaction previously created by `create_transaction` and sends it with
+    /// `send_with_rebroadcast`.
+    pub fn finish_transaction_with_rebroadcast(&self, private_key: &Keypair, message: Message, config: &RebroadcastConfig) -> Result<SendOutcome, Error> {
+        let mut transaction = Transaction::new_unsigned(message);
+        let recent_blockhash = transaction.message.recent_blockhash;
+        transaction.try_sign(&[private_key], recent_blockhash)?;
+
+        self.send_with_rebroadcast(&transaction, config)
+    }
+
+    /// Sends a signed transaction and sends it again every `config.interval` until it reaches
+    /// `config.commitment` or expires.
+    ///
+    /// A transaction expires once the finalized block height passes the last valid block height of
+    /// its blockhash, or, for a durable nonce transaction, once the nonce has been advanced by
+    /// another transaction. Before returning `SendOutcome::Expired` the full status history is
+    /// checked, so a transaction that did land is never reported as expired. A transaction the
+    /// history shows as processed but that doesn't reach `config.commitment` within 30 seconds of
+    /// expiring fails with `Error::ConfirmationTimeout`.
+    ///
+    /// Failed requests after the first send are retried on the next poll. Once a status or expiry
+    /// request failed 10 times in a row, its error is returned instead.
+    pub fn send_with_rebroadcast(&self, transaction: &Transaction, config: &RebroadcastConfig) -> Result<SendOutcome, Error> {
+        let rpc_client = self.rpc_client();
+        let expiry = self.expiry(transaction, config)?;
+        let rebroadcast_config = RpcSendTransactionConfig {
+            skip_preflight: true,
+            ..RpcSendTransactionConfig::default()
+        };
+
+        let signature = rpc_client.send_transaction(transaction)?;
+        let mut last_sent = Instant::now();
+        let mut expired_at = None;
+        let mut status_failures = FailureCount::default();
+        let mut expiry_failures = FailureCount::default();
+        let mut history_failures = FailureCount::default();
+
+        loop {
+            let status = match status_failures.check(rpc_client.get_signature_statuses(&[signature]))? {
+                Some(response) => response.value.into_iter().next().flatten(),
+                None => {
+                    std::thread::sleep(POLL_INTERVAL);
+                    continue;
+                }
+            };
+            let seen = status.is_some();
+            if let Ok(confirmation) = check_confirmation(&signature, status, config.commitment) {
+                return Ok(SendOutcome::Confirmed(confirmation));
+            }
+
+            if !seen && expired_at.is_none() {
+                if expiry_failures.check(self.is_expired(&expiry))?.unwrap_or(false) {
+                    expired_at = Some(Instant::now());
+                } else if last_sent.elapsed() >= config.interval {
+                    // The transaction was already accepted once, so errors here are transient.
+                    let _ = rpc_client.send_transaction_with_config(transaction, rebroadcast_config);
+                    last_sent = Instant::now();
+                }
+            }
+
+            if let Some(expired_at) = expired_at {
+                if let Some(response) = history_failures.check(rpc_client.get_signature_statuses_with_history(&[signature]))? {
+                    let status = response.value.into_iter().next().flatten();
+                    match check_confirmation(&signature, status, config.commitment) {
+                        Ok(confirmation) => return Ok(SendOutcome::Confirmed(confirmation)),
+                        Err(ConfirmationProgress::NotFound) => return Ok(SendOutcome::Expired(signature)),
+                        Err(ConfirmationProgress::Pending { .. }) => {}
+                    }
+                }
+                if expired_at.elapsed() >= EXPIRY_GRACE_PERIOD {
+                    return Err(Error::ConfirmationTimeout(signature));
+                }
+            }
+
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    fn expiry(&self, transaction: &Transaction, config: &RebroadcastConfig) -> Result<Expiry, Error> {
+        let message = &transaction.message;
+
+        if let Some(ix) = uses_durable_nonce(transaction) {
+            let account = ix
+                .accounts
+                .first()
+                .and_then(|index| message.account_keys.get(*index as usize))
+                .ok_or_else(|| Error::InvalidTransaction("missing nonce account".to_string()))?;
+            return Ok(Expiry::Nonce {
+                account: *account,
+                nonce: message.recent_blockhash,
+            });
+        }
+
+        if let Some(last_valid_block_height) = config.last_valid_block_height {
+            return Ok(Expiry::BlockHeight(last_valid_block_height));
+        }
+        let (latest_blockhash, last_valid_block_height) = get_latest_blockhash(self.rpc_client(), CommitmentConfig::finalized())?;
+        if latest_blockhash == message.recent_blockhash {
+            Ok(Expiry::BlockHeight(last_valid_block_height))
+        } else {
+            Ok(Expiry::Blockhash(message.recent_blockhash))
+        }
+    }
+
+    /// Checks expiry against finalized state, which can't be rolled back.
+    fn is_expired(&self, expiry: &Expiry) -> Result<bool, Error> {
+        let rpc_client = self.rpc_client();
+        let commitment = CommitmentConfig::finalized();
+
+        Ok(match expiry {
+            Expiry::BlockHeight(last_valid_block_height) => {
+                rpc_client.get_block_height_with_commitment(commitment)? > *last_valid_block_height
+            }
+            Expiry::Blockhash(blockhash) => !is_blockhash_valid(rpc_client, blockhash, commitment)?,
+            Expiry::Nonce { account, nonce } => {
+                let account = nonce_utils::get_account_with_commitment(rpc_client, account, commitment)?;
+                nonce_utils::data_from_account(&account)?.blockhash != *nonce
+            }
+        })
+    }
+}
+
+/// Returns the confirmation if `status` satisfies `commitment`, or the progress so far.
+pub(crate) fn check_confirmation(
+    signature: &Signature,
+    status: Option<TransactionStatus>,
+    commitment: CommitmentConfig,
+) -> Result<TransactionConfirmation, ConfirmationProgress> {
+    match status {
+        Some(status) if status.satisfies_commitment(commitment) => Ok(TransactionConfirmation {
+            signature: *signature,
+            slot: status.slot,
+            confirmation_status: status.confirmation_status,
+            status: status.status,
+        }),
+        Some(status) => Err(ConfirmationProgress::Pending {
+            slot: status.slot,
+            confirmation_status: status.confirmation_status,
+            confirmations: status.confirmations,
+        }),
+        None => Err(ConfirmationProgress::NotFound),
+    }
+}
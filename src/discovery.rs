@@ -0,0 +1,151 @@
+//! Finding the accounts of a restored mnemonic.
+//!
+//! Wallets derive their accounts along different paths, see `keys::DerivationPreset`, and users
+//! rarely know which one theirs used. `PayClient::discover_accounts` scans the presets the way
+//! BIP44 account discovery does: account indices are checked in order until `gap_limit`
+//! consecutive ones are unused. `scan_accounts` runs the same scan with any account check.
+
+use crate::keys::{derive_keypair_from_seed, mnemonic_seed, DerivationPreset};
+use crate::token::TokenBalance;
+use crate::{fetch_signatures, map_concurrently, Error, Lamports, PayClient};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{derivation_path::DerivationPath, signature::Signer};
+use std::convert::TryFrom;
+
+/// Settings of `PayClient::discover_accounts`.
+#[derive(Debug, Clone)]
+pub struct DiscoveryConfig {
+    /// Path styles to scan, in order.
+    pub presets: Vec<DerivationPreset>,
+    /// Number of consecutive unused account indices after which a preset is considered done. Must
+    /// be at least 1.
+    pub gap_limit: u32,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            presets: DerivationPreset::ALL.to_vec(),
+            gap_limit: 20,
+        }
+    }
+}
+
+/// An account found by `PayClient::discover_accounts`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoveredAccount {
+    pub preset: DerivationPreset,
+    pub index: u32,
+    /// Pass to `keys::derive_keypair` to get the keypair.
+    pub path: DerivationPath,
+    pub address: Pubkey,
+    pub balance: Lamports,
+    pub token_balances: Vec<TokenBalance>,
+    /// Whether any transaction references the address.
+    pub has_history: bool,
+}
+
+impl DiscoveredAccount {
+    /// Returns whether the account holds or ever held anything.
+    pub fn is_used(&self) -> bool {
+        self.balance > Lamports::ZERO || !self.token_balances.is_empty() || self.has_history
+    }
+}
+
+/// An account index that `scan_accounts` couldn't check.
+#[derive(Debug)]
+pub struct FailedCheck {
+    pub preset: DerivationPreset,
+    pub index: u32,
+    pub error: Error,
+}
+
+/// Result of `PayClient::discover_accounts` and `scan_accounts`.
+#[derive(Debug, Default)]
+pub struct Discovery {
+    /// Used accounts, ordered by preset then index.
+    pub accounts: Vec<DiscoveredAccount>,
+    /// Indices whose check failed, in the same order. They count as unused towards the gap limit,
+    /// so scanning again after a failure may find more accounts.
+    pub failed: Vec<FailedCheck>,
+}
+
+/// Scans `config.presets` for used accounts, with `check` returning the account at an index of a
+/// preset. Up to `max_concurrent_checks` indices are checked at a time, but never beyond the point
+/// where `config.gap_limit` consecutive unused indices would end the preset, so `check` is called
+/// for the same indices as in a sequential scan.
+///
+/// Fails with `Error::BadParameter` if `config.gap_limit` is 0.
+pub fn scan_accounts<F>(config: &DiscoveryConfig, max_concurrent_checks: usize, check: F) -> Result<Discovery, Error>
+where
+    F: Fn(DerivationPreset, u32) -> Result<DiscoveredAccount, Error> + Sync,
+{
+    if config.gap_limit == 0 {
+        return Err(Error::BadParameter("gap_limit must be at least 1".to_string()));
+    }
+
+    let mut discovery = Discovery::default();
+    for &preset in &config.presets {
+        let mut next_index = 0;
+        let mut unused = 0;
+        while unused < config.gap_limit {
+            let count = if preset.has_account_index() {
+                (config.gap_limit - unused).min(u32::try_from(max_concurrent_checks.max(1)).unwrap_or(u32::MAX))
+            } else {
+                1
+            };
+            let indices: Vec<u32> = (next_index..next_index + count).collect();
+
+            for (index, result) in indices.iter().zip(map_concurrently(&indices, count as usize, |&index| check(preset, index))) {
+                match result {
+                    Ok(account) if account.is_used() => {
+                        discovery.accounts.push(account);
+                        unused = 0;
+                    }
+                    Ok(_) => unused += 1,
+                    Err(error) => {
+                        discovery.failed.push(FailedCheck { preset, index: *index, error });
+                        unused += 1;
+                    }
+                }
+            }
+
+            if !preset.has_account_index() {
+                break;
+            }
+            next_index += count;
+        }
+    }
+
+    Ok(discovery)
+}
+
+impl PayClient {
+    /// Derives the accounts of a BIP39 mnemonic along `config.presets` and returns those with a
+    /// balance, token accounts or transaction history. Indices are checked concurrently, see
+    /// `PayClientConfig::max_concurrent_requests`, and one that fails to load is reported in
+    /// `Discovery::failed` instead of failing the whole scan.
+    ///
+    /// Each derived address costs a few requests, so a low `config.gap_limit` is much faster when
+    /// the wallet is known to use consecutive accounts. Fails with `Error::BadParameter` if it is 0.
+    pub fn discover_accounts(&self, phrase: &str, passphrase: &str, config: &DiscoveryConfig) -> Result<Discovery, Error> {
+        let seed = mnemonic_seed(phrase, passphrase)?;
+        scan_accounts(config, self.max_concurrent_requests(), |preset, index| self.check_account(&seed, preset, index))
+    }
+
+    fn check_account(&self, seed: &[u8], preset: DerivationPreset, index: u32) -> Result<DiscoveredAccount, Error> {
+        let path = preset.path(index);
+        let address = derive_keypair_from_seed(seed, &path)?.pubkey();
+        let has_history = !fetch_signatures(self.rpc_client(), self.history_commitment(), &address, None, None, 1)?.is_empty();
+
+        Ok(DiscoveredAccount {
+            preset,
+            index,
+            path,
+            address,
+            balance: self.get_balance(&address.to_string())?,
+            token_balances: self.get_token_balances(&address.to_string())?,
+            has_history,
+        })
+    }
+}
@@ -0,0 +1,77 @@
+use crate::keys::KeyError;
+use crate::recipient::RecipientWarning;
+use crate::{Lamports, TokenAmount};
+use solana_client::client_error::ClientError;
+use solana_client::nonce_utils;
+use solana_program::pubkey::{ParsePubkeyError, Pubkey};
+use solana_sdk::signature::{ParseSignatureError, Signature};
+use solana_sdk::signer::SignerError;
+use thiserror::Error;
+
+/// Errors returned by the public API.
+///
+/// Amounts carried by the variants are `Lamports` for SOL and `TokenAmount` for tokens.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("Bad parameter: {0}")]
+    BadParameter(String),
+    #[error(transparent)]
+    ClientError(#[from] ClientError),
+    #[error("Transaction {0} did not reach the requested commitment in time")]
+    ConfirmationTimeout(Signature),
+    /// `(fee, fee_payer)`
+    #[error("Account {1} has insufficient funds for fee ({0} SOL)")]
+    InsufficientFundsForFee(Lamports, Pubkey),
+    /// `(spend, sender)`
+    #[error("Account {1} has insufficient funds for spend ({0} SOL)")]
+    InsufficientFundsForSpend(Lamports, Pubkey),
+    /// `(spend, fee, sender)`
+    #[error("Account {2} has insufficient funds for spend ({0} SOL) + fee ({1} SOL)")]
+    InsufficientFundsForSpendAndFee(Lamports, Lamports, Pubkey),
+    /// `(amount, token_account)`
+    #[error("Token account {1} has insufficient funds for spend ({0})")]
+    InsufficientTokenFunds(TokenAmount, Pubkey),
+    #[error("Invalid amount: {0}")]
+    InvalidAmount(String),
+    #[error("Invalid private key: {0}")]
+    InvalidKey(#[from] KeyError),
+    #[error("Invalid public key: {0}")]
+    InvalidPubkey(#[from] ParsePubkeyError),
+    #[error("Invalid transaction: {0}")]
+    InvalidTransaction(String),
+    #[error("{0} is not an initialized token mint")]
+    InvalidMint(Pubkey),
+    #[error(transparent)]
+    InvalidNonce(nonce_utils::Error),
+    #[error("Invalid signature: {0}")]
+    InvalidSignature(#[from] ParseSignatureError),
+    /// `(memo_len, max_len)` in bytes
+    #[error("Memo is {0} bytes long, at most {1} bytes fit in the transaction")]
+    MemoTooLong(usize, usize),
+    /// `(balance, required, sender)`, where `required` is the reserve of a `SpendAmount`, plus the
+    /// fee when the sender pays it
+    #[error("Account {2} has {0} SOL, leaving nothing to send after keeping {1} SOL for the reserve and fee")]
+    NothingToSend(Lamports, Lamports, Pubkey),
+    /// `(spend, rent_exempt_minimum, recipient)`
+    #[error("Account {2} does not exist yet and needs at least {1} SOL to be created, got {0} SOL")]
+    RecipientNotRentExempt(Lamports, Lamports, Pubkey),
+    #[error("RPC request error: {0}")]
+    RpcRequestError(String),
+    #[error("Refusing to send to {0}: {1}")]
+    RiskyRecipient(Pubkey, RecipientWarning),
+    /// `(remaining, rent_exempt_minimum, account)`, for the sender or the fee payer
+    #[error("Account {2} would keep {0} SOL, below the rent-exempt minimum of {1} SOL; send everything or leave more")]
+    SenderNotRentExempt(Lamports, Lamports, Pubkey),
+    #[error("Signing failed: {0}")]
+    SignerError(#[from] SignerError),
+}
+
+impl From<nonce_utils::Error> for Error {
+    fn from(error: nonce_utils::Error) -> Self {
+        match error {
+            nonce_utils::Error::Client(client_error) => Self::RpcRequestError(client_error),
+            _ => Self::InvalidNonce(error),
+        }
+    }
+}
@@ -0,0 +1,523 @@
+//! Transaction history: lazy pagination and typed payment records.
+
+use crate::amount::SOL_DECIMALS;
+use crate::token::{is_token_program, TRANSFER, TRANSFER_CHECKED};
+use crate::{Error, Lamports, PayClient, TokenAmount};
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{
+    clock::{Slot, UnixTimestamp},
+    signature::Signature,
+    system_instruction::SystemInstruction,
+    system_program,
+    transaction::{Transaction, TransactionError},
+};
+use solana_transaction_status::{EncodedConfirmedTransactionWithStatusMeta, UiTransactionTokenBalance};
+use std::collections::{BTreeMap, VecDeque};
+use std::convert::TryInto;
+use std::fmt;
+use std::str::FromStr;
+
+/// Most signatures `getSignaturesForAddress` returns per request.
+const MAX_PAGE_SIZE: usize = 1000;
+
+/// An opaque position in a wallet's history, used to resume a `TransactionHistory` where it
+/// stopped, e.g. when an infinite-scroll screen is reopened.
+///
+/// It can be persisted with `to_string` and restored with `parse`. The default cursor starts at
+/// the latest transaction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HistoryCursor(Option<Signature>);
+
+impl fmt::Display for HistoryCursor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.0 {
+            Some(signature) => write!(f, "{}", signature),
+            None => Ok(()),
+        }
+    }
+}
+
+impl FromStr for HistoryCursor {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            Ok(Self(None))
+        } else {
+            Ok(Self(Some(s.parse()?)))
+        }
+    }
+}
+
+/// Iterator over a wallet's transactions from latest to earliest, created by
+/// `PayClient::transaction_history`.
+///
+/// Signatures are requested one page at a time as the iterator advances. Transactions are fetched
+/// in batches of `PayClientConfig::max_concurrent_requests` running in parallel, so stopping early
+/// wastes at most one batch.
+///
+/// A transaction that fails to load is yielded once as a `HistoryEntry` holding the error, and the
+/// iterator moves on to the next one. A page of signatures that fails to load is yielded as an
+/// error and ends the iteration; resume it from `cursor` with a new `TransactionHistory`.
+pub struct TransactionHistory<'a> {
+    client: &'a PayClient,
+    address: Pubkey,
+    until: Option<Signature>,
+    page_size: usize,
+    cursor: HistoryCursor,
+    /// Signatures of the current page that haven't been fetched yet.
+    page: VecDeque<Signature>,
+    /// Fetched transactions that haven't been yielded yet.
+    batch: VecDeque<HistoryEntry>,
+    last_page: bool,
+    /// Set once a page failed to load.
+    failed: bool,
+}
+
+impl TransactionHistory<'_> {
+    /// Returns the position after the last yielded entry, whether or not its transaction loaded.
+    pub fn cursor(&self) -> HistoryCursor {
+        self.cursor
+    }
+
+    fn fetch_page(&mut self) -> Result<(), Error> {
+        let results = self.client.rpc_client().get_signatures_for_address_with_config(
+            &self.address,
+            GetConfirmedSignaturesForAddress2Config {
+                before: self.cursor.0,
+                until: self.until,
+                limit: Some(self.page_size),
+                commitment: Some(self.client.history_commitment()),
+            },
+        )?;
+
+        self.last_page = results.len() < self.page_size;
+        for result in results {
+            self.page.push_back(result.signature.parse()?);
+        }
+
+        Ok(())
+    }
+
+    fn fetch_batch(&mut self) {
+        let count = self.page.len().min(self.client.max_concurrent_requests().max(1));
+        let signatures: Vec<Signature> = self.page.drain(..count).collect();
+        let transactions = self.client.fetch_transactions(&signatures);
+
+        self.batch.extend(
+            signatures
+                .into_iter()
+                .zip(transactions)
+                .map(|(signature, transaction)| HistoryEntry { signature, transaction }),
+        );
+    }
+}
+
+impl Iterator for TransactionHistory<'_> {
+    type Item = Result<HistoryEntry, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        if self.batch.is_empty() {
+            if self.page.is_empty() && !self.last_page {
+                if let Err(e) = self.fetch_page() {
+                    self.failed = true;
+                    return Some(Err(e));
+                }
+            }
+            self.fetch_batch();
+        }
+
+        let entry = self.batch.pop_front()?;
+        self.cursor = HistoryCursor(Some(entry.signature));
+        Some(Ok(entry))
+    }
+}
+
+/// A transaction of `PayClient::get_transaction_history` or `TransactionHistory`, or the error that
+/// prevented fetching it.
+#[derive(Debug)]
+pub struct HistoryEntry {
+    pub signature: Signature,
+    pub transaction: Result<EncodedConfirmedTransactionWithStatusMeta, Error>,
+}
+
+/// Which way a payment moved relative to the wallet whose history is parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Incoming,
+    Outgoing,
+    /// The wallet paid itself, or only paid the fee.
+    SelfTransfer,
+}
+
+/// What was transferred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Asset {
+    Sol,
+    Token { mint: Pubkey, decimals: u8 },
+}
+
+/// A single history entry, seen from one wallet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaymentRecord {
+    pub signature: Signature,
+    pub slot: Slot,
+    pub block_time: Option<UnixTimestamp>,
+    pub direction: Direction,
+    /// The other side of the payment, if it could be identified.
+    pub counterparty: Option<Pubkey>,
+    pub asset: Asset,
+    /// Amount moved, with 9 decimals for SOL or the mint's decimals, or the amount a failed
+    /// transaction tried to move. The fee is not included.
+    pub amount: TokenAmount,
+    /// Network fee, paid by `fee_payer`.
+    pub fee: Lamports,
+    pub fee_payer: Pubkey,
+    pub memo: Option<String>,
+    /// The outcome of the transaction. Failed transactions moved nothing but still paid the fee.
+    pub status: Result<(), TransactionError>,
+}
+
+impl PayClient {
+    /// Returns an iterator over the transactions of `address`, from latest to earliest, starting
+    /// after `cursor` and stopping at `until` (exclusive). Pages of `page_size` signatures are
+    /// fetched as needed; it must be between 1 and 1000.
+    pub fn transaction_history(
+        &self,
+        address: &Pubkey,
+        cursor: HistoryCursor,
+        until: Option<Signature>,
+        page_size: usize,
+    ) -> Result<TransactionHistory, Error> {
+        if !(1..=MAX_PAGE_SIZE).contains(&page_size) {
+            return Err(Error::BadParameter(format!(
+                "page_size must be between 1 and {}, got {}",
+                MAX_PAGE_SIZE, page_size
+            )));
+        }
+
+        Ok(TransactionHistory {
+            client: self,
+            address: *address,
+            until,
+            page_size,
+            cursor,
+            page: VecDeque::new(),
+            batch: VecDeque::new(),
+            last_page: false,
+            failed: false,
+        })
+    }
+
+    /// Like `process_transaction_history`, with each entry parsed into a `PaymentRecord` from the
+    /// point of view of `address`.
+    pub fn get_payment_history(
+        &self,
+        address: &Pubkey,
+        before: Option<Signature>,
+        until: Option<Signature>,
+        limit: usize,
+    ) -> Result<Vec<PaymentRecord>, Error> {
+        self.process_transaction_history(address, before, until, limit)?
+            .iter()
+            .map(|entry| parse_payment(address, entry))
+            .collect()
+    }
+}
+
+/// Parses a history entry, fetched with base64 encoding as `process_transaction_history` does, into
+/// a `PaymentRecord` from the point of view of `address`.
+///
+/// Token transfers are detected from the token balances of accounts owned by `address`; otherwise
+/// the entry is treated as a SOL payment. Failed transactions change no balance but the fee, so
+/// their record describes the first transfer instruction involving `address` instead.
+pub fn parse_payment(address: &Pubkey, entry: &EncodedConfirmedTransactionWithStatusMeta) -> Result<PaymentRecord, Error> {
+    let transaction = entry
+        .transaction
+        .transaction
+        .decode()
+        .ok_or_else(|| Error::InvalidTransaction("unable to decode transaction".to_string()))?;
+    let meta = entry
+        .transaction
+        .meta
+        .as_ref()
+        .ok_or_else(|| Error::InvalidTransaction("missing transaction status".to_string()))?;
+    let account_keys = &transaction.message.account_keys;
+    let fee_payer = *account_keys
+        .first()
+        .ok_or_else(|| Error::InvalidTransaction("transaction has no accounts".to_string()))?;
+    let pre_token_balances = meta.pre_token_balances.as_deref().unwrap_or_default();
+    let post_token_balances = meta.post_token_balances.as_deref().unwrap_or_default();
+    // Payments into an existing token account only list the account, not its owner.
+    let owns_token_account = pre_token_balances
+        .iter()
+        .chain(post_token_balances)
+        .any(|balance| balance.owner.as_deref().and_then(|owner| Pubkey::from_str(owner).ok()) == Some(*address));
+    if !account_keys.contains(address) && !owns_token_account {
+        return Err(Error::BadParameter(format!("{} is not part of the transaction", address)));
+    }
+
+    let (asset, direction, counterparty, amount) = if meta.status.is_err() {
+        parse_failed_payment(address, &transaction, pre_token_balances)
+    } else {
+        let token_payment = parse_token_payment(address, account_keys, pre_token_balances, post_token_balances);
+        match token_payment {
+            Some(token_payment) => token_payment,
+            None => parse_sol_payment(address, &transaction, &meta.pre_balances, &meta.post_balances, meta.fee),
+        }
+    };
+    let decimals = match asset {
+        Asset::Sol => SOL_DECIMALS,
+        Asset::Token { decimals, .. } => decimals,
+    };
+
+    Ok(PaymentRecord {
+        signature: transaction.signatures.first().copied().unwrap_or_default(),
+        slot: entry.slot,
+        block_time: entry.block_time,
+        direction,
+        counterparty,
+        asset,
+        amount: TokenAmount::new(amount, decimals),
+        fee: Lamports(meta.fee),
+        fee_payer,
+        memo: parse_memo(&transaction),
+        status: meta.status.clone(),
+    })
+}
+
+type ParsedPayment = (Asset, Direction, Option<Pubkey>, u64);
+
+fn parse_sol_payment(address: &Pubkey, transaction: &Transaction, pre_balances: &[u64], post_balances: &[u64], fee: u64) -> ParsedPayment {
+    // Balance changes with the fee added back, so only transfers remain.
+    let deltas: Vec<i128> = pre_balances
+        .iter()
+        .zip(post_balances)
+        .enumerate()
+        .map(|(i, (pre, post))| {
+            let fee = if i == 0 { fee } else { 0 };
+            *post as i128 - *pre as i128 + fee as i128
+        })
+        .collect();
+    let account_keys = &transaction.message.account_keys;
+    // `address` may only own token accounts of the transaction, leaving its SOL balance untouched.
+    let index = account_keys.iter().position(|key| key == address);
+    let delta = index.and_then(|index| deltas.get(index)).copied().unwrap_or_default();
+
+    let counterparty = |sign: i128| {
+        deltas
+            .iter()
+            .enumerate()
+            .filter(|(i, delta)| Some(*i) != index && delta.signum() == sign)
+            .max_by_key(|(_, delta)| delta.abs())
+            .map(|(i, _)| account_keys[i])
+    };
+
+    if delta > 0 {
+        (Asset::Sol, Direction::Incoming, counterparty(-1), delta as u64)
+    } else if delta < 0 {
+        (Asset::Sol, Direction::Outgoing, counterparty(1), delta.unsigned_abs() as u64)
+    } else {
+        (Asset::Sol, Direction::SelfTransfer, None, self_transfer_lamports(address, transaction))
+    }
+}
+
+/// Sums the system transfers from `address` to itself, which leave no trace in the balances.
+fn self_transfer_lamports(address: &Pubkey, transaction: &Transaction) -> u64 {
+    instruction_transfers(transaction, &[])
+        .iter()
+        .filter(|transfer| transfer.asset == Asset::Sol && transfer.from == *address && transfer.to == *address)
+        .map(|transfer| transfer.amount)
+        .sum()
+}
+
+/// Describes what a failed transaction attempted from its transfer instructions, summing those
+/// that moved the same asset in the same direction as the first one involving `address`.
+fn parse_failed_payment(address: &Pubkey, transaction: &Transaction, pre_token_balances: &[UiTransactionTokenBalance]) -> ParsedPayment {
+    let direction = |transfer: &InstructionTransfer| match (transfer.from == *address, transfer.to == *address) {
+        (true, true) => Some(Direction::SelfTransfer),
+        (true, false) => Some(Direction::Outgoing),
+        (false, true) => Some(Direction::Incoming),
+        (false, false) => None,
+    };
+
+    let transfers = instruction_transfers(transaction, pre_token_balances);
+    let first = match transfers.iter().find(|transfer| direction(transfer).is_some()) {
+        Some(first) => first,
+        None => return (Asset::Sol, Direction::SelfTransfer, None, 0),
+    };
+    let first_direction = direction(first);
+    let counterparty = match first_direction {
+        Some(Direction::Outgoing) => Some(first.to),
+        Some(Direction::Incoming) => Some(first.from),
+        _ => None,
+    };
+    let amount = transfers
+        .iter()
+        .filter(|transfer| transfer.asset == first.asset && direction(transfer) == first_direction)
+        .map(|transfer| transfer.amount)
+        .fold(0u64, u64::saturating_add);
+
+    (first.asset, first_direction.unwrap_or(Direction::SelfTransfer), counterparty, amount)
+}
+
+/// A transfer decoded from an instruction, between wallet addresses.
+struct InstructionTransfer {
+    asset: Asset,
+    from: Pubkey,
+    to: Pubkey,
+    amount: u64,
+}
+
+/// Decodes the system and token transfer instructions of `transaction`. Token accounts are
+/// replaced by their owner when `pre_token_balances` has it; the mint and decimals of a plain
+/// `Transfer` are only known from there too.
+fn instruction_transfers(transaction: &Transaction, pre_token_balances: &[UiTransactionTokenBalance]) -> Vec<InstructionTransfer> {
+    let message = &transaction.message;
+    let token_balance = |key: &Pubkey| {
+        pre_token_balances
+            .iter()
+            .find(|balance| message.account_keys.get(balance.account_index as usize) == Some(key))
+    };
+    let owner = |key: &Pubkey| {
+        token_balance(key)
+            .and_then(|balance| balance.owner.as_ref())
+            .and_then(|owner| Pubkey::from_str(owner).ok())
+            .unwrap_or(*key)
+    };
+
+    message
+        .instructions
+        .iter()
+        .filter_map(|ix| {
+            let program_id = message.account_keys.get(ix.program_id_index as usize)?;
+            let account = |position: usize| message.account_keys.get(*ix.accounts.get(position)? as usize);
+
+            if system_program::check_id(program_id) {
+                match bincode::deserialize(&ix.data).ok()? {
+                    SystemInstruction::Transfer { lamports } => Some(InstructionTransfer {
+                        asset: Asset::Sol,
+                        from: *account(0)?,
+                        to: *account(1)?,
+                        amount: lamports,
+                    }),
+                    _ => None,
+                }
+            } else if is_token_program(program_id) {
+                let amount = u64::from_le_bytes(ix.data.get(1..9)?.try_into().ok()?);
+                let (source, destination, asset) = match *ix.data.first()? {
+                    TRANSFER => {
+                        let balance = token_balance(account(0)?)?;
+                        let mint = Pubkey::from_str(&balance.mint).ok()?;
+                        let decimals = balance.ui_token_amount.decimals;
+                        (account(0)?, account(1)?, Asset::Token { mint, decimals })
+                    }
+                    TRANSFER_CHECKED => {
+                        let decimals = *ix.data.get(9)?;
+                        (account(0)?, account(2)?, Asset::Token { mint: *account(1)?, decimals })
+                    }
+                    _ => return None,
+                };
+                Some(InstructionTransfer {
+                    asset,
+                    from: owner(source),
+                    to: owner(destination),
+                    amount,
+                })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+fn parse_token_payment(
+    address: &Pubkey,
+    account_keys: &[Pubkey],
+    pre_token_balances: &[UiTransactionTokenBalance],
+    post_token_balances: &[UiTransactionTokenBalance],
+) -> Option<ParsedPayment> {
+    struct TokenDelta {
+        mint: Pubkey,
+        decimals: u8,
+        owner: Option<Pubkey>,
+        delta: i128,
+    }
+
+    // Balance change of every token account, keyed by account index.
+    let mut deltas: BTreeMap<u8, TokenDelta> = BTreeMap::new();
+    for (balances, sign) in [(pre_token_balances, -1), (post_token_balances, 1)] {
+        for balance in balances {
+            let mint = Pubkey::from_str(&balance.mint).ok()?;
+            let amount: i128 = balance.ui_token_amount.amount.parse().ok()?;
+            let entry = deltas.entry(balance.account_index).or_insert_with(|| TokenDelta {
+                mint,
+                decimals: balance.ui_token_amount.decimals,
+                owner: balance
+                    .owner
+                    .as_ref()
+                    .and_then(|owner| Pubkey::from_str(owner).ok())
+                    .or_else(|| account_keys.get(balance.account_index as usize).copied()),
+                delta: 0,
+            });
+            entry.delta += sign * amount;
+        }
+    }
+
+    let own = deltas
+        .values()
+        .find(|delta| delta.owner.as_ref() == Some(address) && delta.delta != 0)?;
+    let sign = own.delta.signum();
+    let delta: i128 = deltas
+        .values()
+        .filter(|delta| delta.owner.as_ref() == Some(address) && delta.mint == own.mint)
+        .map(|delta| delta.delta)
+        .sum();
+    let counterparty = deltas
+        .values()
+        .filter(|delta| delta.mint == own.mint && delta.owner.as_ref() != Some(address) && delta.delta.signum() == -sign)
+        .max_by_key(|delta| delta.delta.abs())
+        .and_then(|delta| delta.owner);
+
+    let direction = match delta.signum() {
+        1 => Direction::Incoming,
+        -1 => Direction::Outgoing,
+        _ => Direction::SelfTransfer,
+    };
+    let asset = Asset::Token {
+        mint: own.mint,
+        decimals: own.decimals,
+    };
+
+    Some((asset, direction, counterparty, delta.unsigned_abs() as u64))
+}
+
+fn parse_memo(transaction: &Transaction) -> Option<String> {
+    let memo_program_ids = [
+        Pubkey::new(&spl_memo::id().to_bytes()),
+        Pubkey::new(&spl_memo::v1::id().to_bytes()),
+    ];
+    let message = &transaction.message;
+
+    let memos: Vec<String> = message
+        .instructions
+        .iter()
+        .filter(|ix| {
+            message
+                .account_keys
+                .get(ix.program_id_index as usize)
+                .is_some_and(|program_id| memo_program_ids.contains(program_id))
+        })
+        .map(|ix| String::from_utf8_lossy(&ix.data).into_owned())
+        .collect();
+
+    if memos.is_empty() {
+        None
+    } else {
+        Some(memos.join("; "))
+    }
+}
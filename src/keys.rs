@@ -0,0 +1,245 @@
+//! Importing private keys pasted or loaded by users.
+//!
+//! Wallets export keys in several formats. `import_key` recognizes them without being told which
+//! one it got, and never panics on malformed input, unlike `Keypair::from_base58_string`.
+//!
+//! Mnemonics can also be derived along BIP44 paths with `derive_keypair`, to find the same
+//! addresses other wallets show for them.
+//!
+//! A 32-byte secret seed is accepted by `import_key` only as a JSON byte array, since a base58
+//! string of 32 bytes is almost always an address pasted by mistake. Seeds exported as base58 or
+//! hex are imported explicitly with `import_seed`.
+
+use crate::Error;
+use bip39::{Language, Mnemonic};
+use solana_sdk::{
+    bs58,
+    derivation_path::DerivationPath,
+    signature::{
+        generate_seed_from_seed_phrase_and_passphrase, keypair_from_seed, keypair_from_seed_and_derivation_path, Keypair,
+        Signer,
+    },
+};
+use std::path::Path;
+use thiserror::Error;
+
+const SECRET_KEY_LENGTH: usize = 32;
+const KEYPAIR_LENGTH: usize = 64;
+const HARDENED_OFFSET: u32 = 1 << 31;
+
+/// The format `import_key` recognized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyFormat {
+    /// A base58 encoded 64-byte keypair, as exported by Phantom or Solflare.
+    Base58,
+    /// A JSON array of bytes, as written by `solana-keygen`, e.g. `testnet_key.json`.
+    JsonByteArray,
+    /// A BIP39 mnemonic, converted like `solana-keygen recover` does, without derivation path.
+    /// Other wallets derive their addresses from it, see `derive_keypair`.
+    Mnemonic,
+}
+
+/// Why a private key couldn't be imported.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[non_exhaustive]
+pub enum KeyError {
+    #[error("the key is empty")]
+    Empty,
+    #[error("the key is not base58, a JSON byte array or a mnemonic")]
+    UnrecognizedFormat,
+    #[error("invalid JSON byte array: {0}")]
+    InvalidJson(String),
+    /// The number of bytes, which must be 32 for a seed or 64 for a keypair.
+    #[error("expected 32 or 64 bytes, got {0}")]
+    InvalidLength(usize),
+    /// The second half of a 64-byte keypair is not the public key of the first half.
+    #[error("the public key doesn't match the secret key")]
+    PublicKeyMismatch,
+    /// A base58 string of 32 bytes is almost always an address rather than a secret. Use
+    /// `import_seed` if it really is a seed.
+    #[error("this is a public address, not a private key")]
+    PublicKey,
+    #[error("invalid mnemonic: {0}")]
+    InvalidMnemonic(String),
+    #[error("unable to read the key file: {0}")]
+    Unreadable(String),
+    #[error("invalid derivation path: {0}")]
+    InvalidDerivationPath(String),
+}
+
+/// Derivation path styles used by common wallets for account `n`. Every index is hardened, as
+/// ed25519 keys can't be derived otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DerivationPreset {
+    /// `m/44'/501'/n'/0'`, used by Phantom, Solflare and Ledger Live.
+    Bip44Change,
+    /// `m/44'/501'/n'`, used by some older wallets and by `solana-keygen` with `?key=n`.
+    Bip44,
+    /// `m/44'/501'`, a single account, used by `solana-keygen` with a bare `--derivation-path`.
+    Bip44Root,
+}
+
+impl DerivationPreset {
+    pub const ALL: [DerivationPreset; 3] = [Self::Bip44Change, Self::Bip44, Self::Bip44Root];
+
+    /// Returns the path of account `index`. `Bip44Root` has no account index, so it returns the
+    /// same path for every `index`.
+    pub fn path(self, index: u32) -> DerivationPath {
+        match self {
+            DerivationPreset::Bip44Change => DerivationPath::new_bip44(Some(index), Some(0)),
+            DerivationPreset::Bip44 => DerivationPath::new_bip44(Some(index), None),
+            DerivationPreset::Bip44Root => DerivationPath::new_bip44(None, None),
+        }
+    }
+
+    pub fn has_account_index(self) -> bool {
+        self != DerivationPreset::Bip44Root
+    }
+}
+
+/// A keypair returned by `import_key`.
+#[derive(Debug)]
+pub struct ImportedKey {
+    pub keypair: Keypair,
+    pub format: KeyFormat,
+}
+
+/// Imports a private key in any supported format, detected from its shape: a JSON array, a
+/// phrase of several words or a base58 string. `passphrase` is only used for mnemonics and is
+/// usually empty.
+pub fn import_key(input: &str, passphrase: &str) -> Result<ImportedKey, Error> {
+    let input = input.trim();
+
+    let (keypair, format) = if input.is_empty() {
+        return Err(KeyError::Empty.into());
+    } else if input.starts_with('[') {
+        let bytes: Vec<u8> = serde_json::from_str(input).map_err(|err| KeyError::InvalidJson(err.to_string()))?;
+        (keypair_from_bytes(&bytes)?, KeyFormat::JsonByteArray)
+    } else if input.contains(char::is_whitespace) {
+        (keypair_from_mnemonic(input, passphrase)?, KeyFormat::Mnemonic)
+    } else {
+        (keypair_from_base58(input)?, KeyFormat::Base58)
+    };
+
+    Ok(ImportedKey { keypair, format })
+}
+
+/// Like `import_key`, for the contents of the file at `path`.
+pub fn import_key_file<P: AsRef<Path>>(path: P, passphrase: &str) -> Result<ImportedKey, Error> {
+    let input = std::fs::read_to_string(path).map_err(|err| KeyError::Unreadable(err.to_string()))?;
+
+    import_key(&input, passphrase)
+}
+
+/// Imports a 32-byte secret seed encoded as base58 or as 64 hexadecimal digits. Unlike
+/// `import_key`, this accepts 32-byte base58 strings, so only call it when the user says they are
+/// entering a seed: any address would be accepted as one too.
+pub fn import_seed(input: &str) -> Result<Keypair, Error> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err(KeyError::Empty.into());
+    }
+
+    let bytes = if input.len() == 2 * SECRET_KEY_LENGTH && input.bytes().all(|b| b.is_ascii_hexdigit()) {
+        (0..input.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&input[i..i + 2], 16))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| KeyError::UnrecognizedFormat)?
+    } else {
+        bs58::decode(input)
+            .into_vec()
+            .map_err(|_| KeyError::UnrecognizedFormat)?
+    };
+    if bytes.len() != SECRET_KEY_LENGTH {
+        return Err(KeyError::InvalidLength(bytes.len()).into());
+    }
+
+    keypair_from_bytes(&bytes)
+}
+
+/// Decodes a base58 encoded 64-byte keypair. 32-byte strings are refused as addresses, see
+/// `import_seed`.
+pub fn keypair_from_base58(input: &str) -> Result<Keypair, Error> {
+    let bytes = bs58::decode(input.trim())
+        .into_vec()
+        .map_err(|_| KeyError::UnrecognizedFormat)?;
+    if bytes.len() == SECRET_KEY_LENGTH {
+        return Err(KeyError::PublicKey.into());
+    }
+
+    keypair_from_bytes(&bytes)
+}
+
+/// Builds a keypair from a 64-byte keypair or a 32-byte seed.
+pub fn keypair_from_bytes(bytes: &[u8]) -> Result<Keypair, Error> {
+    if bytes.len() != SECRET_KEY_LENGTH && bytes.len() != KEYPAIR_LENGTH {
+        return Err(KeyError::InvalidLength(bytes.len()).into());
+    }
+    let keypair = keypair_from_seed(&bytes[..SECRET_KEY_LENGTH]).map_err(|_| KeyError::InvalidLength(bytes.len()))?;
+    if bytes.len() == KEYPAIR_LENGTH && keypair.pubkey().to_bytes()[..] != bytes[SECRET_KEY_LENGTH..] {
+        return Err(KeyError::PublicKeyMismatch.into());
+    }
+
+    Ok(keypair)
+}
+
+/// Builds a keypair from an English BIP39 mnemonic the way `solana-keygen recover` does. The words
+/// and their checksum are validated, case and extra whitespace are ignored.
+pub fn keypair_from_mnemonic(phrase: &str, passphrase: &str) -> Result<Keypair, Error> {
+    let seed = mnemonic_seed(phrase, passphrase)?;
+
+    keypair_from_seed(&seed).map_err(|err| KeyError::InvalidMnemonic(err.to_string()).into())
+}
+
+/// Validates `phrase` and returns its 64-byte BIP39 seed.
+pub(crate) fn mnemonic_seed(phrase: &str, passphrase: &str) -> Result<Vec<u8>, Error> {
+    let phrase = phrase
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ");
+    Mnemonic::validate(&phrase, Language::English).map_err(|err| KeyError::InvalidMnemonic(err.to_string()))?;
+
+    Ok(generate_seed_from_seed_phrase_and_passphrase(&phrase, passphrase))
+}
+
+/// Parses a custom Solana derivation path such as `m/44'/501'/3'/1'`. Indices are hardened whether
+/// or not they are marked with `'` or `h`. Only paths below `m/44'/501'` with at most an account
+/// and a change index are supported.
+pub fn parse_derivation_path(path: &str) -> Result<DerivationPath, Error> {
+    let invalid = |reason: &str| KeyError::InvalidDerivationPath(format!("{:?} {}", path, reason));
+
+    let mut components = path.trim().split('/');
+    if components.next() != Some("m") {
+        return Err(invalid("doesn't start with m/").into());
+    }
+    let indices = components
+        .map(|component| {
+            let index = component
+                .strip_suffix(|c: char| c == '\'' || c == 'h')
+                .unwrap_or(component);
+            index.parse::<u32>().ok().filter(|index| *index < HARDENED_OFFSET)
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| invalid("has an invalid index"))?;
+
+    match *indices.as_slice() {
+        [44, 501] => Ok(DerivationPath::new_bip44(None, None)),
+        [44, 501, account] => Ok(DerivationPath::new_bip44(Some(account), None)),
+        [44, 501, account, change] => Ok(DerivationPath::new_bip44(Some(account), Some(change))),
+        _ => Err(invalid("is not of the form m/44'/501'[/account'[/change']]").into()),
+    }
+}
+
+/// Derives the keypair at `path` from an English BIP39 mnemonic, like other wallets do. See
+/// `DerivationPreset` for the common paths.
+pub fn derive_keypair(phrase: &str, passphrase: &str, path: &DerivationPath) -> Result<Keypair, Error> {
+    derive_keypair_from_seed(&mnemonic_seed(phrase, passphrase)?, path)
+}
+
+/// Like `derive_keypair`, from the seed returned by `mnemonic_seed`, which is slow to compute.
+pub(crate) fn derive_keypair_from_seed(seed: &[u8], path: &DerivationPath) -> Result<Keypair, Error> {
+    keypair_from_seed_and_derivation_path(seed, Some(path.clone()))
+        .map_err(|err| KeyError::InvalidDerivationPath(err.to_string()).into())
+}
@@ -1,43 +1,63 @@
 pub use solana_client::rpc_client::{RpcClient, GetConfirmedSignaturesForAddress2Config};
-use solana_client::blockhash_query::BlockhashQuery;
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_client::nonce_utils;
+use solana_client::rpc_request::RpcError;
 pub use solana_program::pubkey::Pubkey;
 use solana_client::rpc_config::RpcTransactionConfig;
 use solana_sdk::signature::Signature;
 use solana_sdk::{
     commitment_config::CommitmentConfig,
-    derivation_path::DerivationPath,
     hash::Hash,
     message::Message,
+    packet::PACKET_DATA_SIZE,
     signature::Signer,
     system_instruction,
-    transaction::Transaction
+    transaction::{uses_durable_nonce, Transaction},
 };
 pub use solana_sdk::native_token::{lamports_to_sol, sol_to_lamports};
 pub use solana_sdk::signature::Keypair;
-use thiserror::Error;
 use spl_memo::id;
 
 use solana_sdk::instruction::Instruction;
 
-use std::str::FromStr;
 use solana_transaction_status::{UiTransactionEncoding, EncodedConfirmedTransactionWithStatusMeta};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
 pub use solana_transaction_status::Encodable;
 pub use solana_sdk::signature::keypair_from_seed_phrase_and_passphrase;
 
+pub mod amount;
+mod client;
+pub mod compute_budget;
+pub mod confirmation;
+pub mod discovery;
+mod error;
+pub mod history;
+pub mod keys;
+pub mod nonce;
+#[cfg(feature = "async")]
+pub mod nonblocking;
+pub mod offline;
+pub mod recipient;
+pub mod simulation;
+pub mod token;
+pub use amount::{Lamports, TokenAmount};
+pub use client::{Cluster, PayClient, PayClientConfig};
+pub use compute_budget::PriorityFee;
+pub use error::Error;
+
 /// Returns the SOL balance of the given wallet address.
-pub fn get_balance(rpc_endpoint: &str, base58_pubkey: &str) -> Result<f64, String> {
-    let rpc_client = RpcClient::new(rpc_endpoint.to_string());
-
-    match Pubkey::from_str(base58_pubkey) {
-        Ok(pubkey) => {
-            let balance_result = rpc_client.get_balance(&pubkey);
-            match balance_result {
-                Ok(balance) => Ok(lamports_to_sol(balance)),
-                Err(e) => Err(format!("Error fetching from RPC client: {}", e)),
-            }
-        },
-        Err(e) => Err(format!("Error getting pub key: {}", e)),
-    }
+#[deprecated(note = "Use `PayClient::get_balance`, which returns exact `Lamports`")]
+pub fn get_balance(rpc_endpoint: &str, base58_pubkey: &str) -> Result<f64, Error> {
+    let balance = PayClient::new(Cluster::Custom(rpc_endpoint.to_string())).get_balance(base58_pubkey)?;
+
+    Ok(lamports_to_sol(balance.lamports()))
+}
+
+/// Returns the SPL Token and Token-2022 accounts of the given wallet address.
+pub fn get_token_balances(rpc_endpoint: &str, base58_pubkey: &str) -> Result<Vec<token::TokenBalance>, Error> {
+    PayClient::new(Cluster::Custom(rpc_endpoint.to_string())).get_token_balances(base58_pubkey)
 }
 
 /// Returns the history of each transaction in order from latest to earliest.
@@ -47,63 +67,226 @@ pub fn process_transaction_history(
     before: Option<Signature>,
     until: Option<Signature>,
     limit: usize,
-) -> Result<Vec<EncodedConfirmedTransactionWithStatusMeta>, Box<dyn std::error::Error>> {
+) -> Result<Vec<EncodedConfirmedTransactionWithStatusMeta>, Error> {
+    let max_concurrent_requests = PayClientConfig::default().max_concurrent_requests;
+    let signatures = fetch_signatures(rpc_client, CommitmentConfig::confirmed(), address, before, until, limit)?;
+
+    fetch_transactions(rpc_client, CommitmentConfig::confirmed(), &signatures, max_concurrent_requests)
+        .into_iter()
+        .collect()
+}
+
+fn signatures_for_address_config(
+    commitment: CommitmentConfig,
+    before: Option<Signature>,
+    until: Option<Signature>,
+    limit: usize,
+) -> GetConfirmedSignaturesForAddress2Config {
+    GetConfirmedSignaturesForAddress2Config {
+        before,
+        until,
+        limit: Some(limit),
+        commitment: Some(commitment),
+    }
+}
+
+fn fetch_signatures(
+    rpc_client: &RpcClient,
+    commitment: CommitmentConfig,
+    address: &Pubkey,
+    before: Option<Signature>,
+    until: Option<Signature>,
+    limit: usize,
+) -> Result<Vec<Signature>, Error> {
     let results = rpc_client.get_signatures_for_address_with_config(
         address,
-        GetConfirmedSignaturesForAddress2Config {
-            before,
-            until,
-            limit: Some(limit),
-            commitment: Some(CommitmentConfig::confirmed()),
-        },
+        signatures_for_address_config(commitment, before, until, limit),
     )?;
-    Ok(results.into_iter().map(|result|
-        rpc_client.get_transaction_with_config(
-            &result.signature.parse::<Signature>().map_err(|op| format!("Unable to parse signature: Err({:?})", op))?,
-            RpcTransactionConfig {
-                encoding: Some(UiTransactionEncoding::Base64),
-                commitment: Some(CommitmentConfig::confirmed()),
-            },
-        ).map_err(|op| format!("Unable to get transaction: Err({:?})", op))
-    ).collect::<Result<Vec<EncodedConfirmedTransactionWithStatusMeta>, String>>()?)
+    results.into_iter().map(|result|
+        Ok(result.signature.parse::<Signature>()?)
+    ).collect()
+}
+
+/// Fetches the given transactions with up to `max_concurrent_requests` requests in flight.
+/// Results are returned in the order of `signatures`, each with its own error.
+fn fetch_transactions(
+    rpc_client: &RpcClient,
+    commitment: CommitmentConfig,
+    signatures: &[Signature],
+    max_concurrent_requests: usize,
+) -> Vec<Result<EncodedConfirmedTransactionWithStatusMeta, Error>> {
+    map_concurrently(signatures, max_concurrent_requests, |signature| fetch_transaction(rpc_client, commitment, signature))
+}
+
+/// Applies `f` to each of `items` on up to `max_concurrent` threads, and returns the results in
+/// the order of `items`.
+fn map_concurrently<T: Sync, R: Send>(items: &[T], max_concurrent: usize, f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let next = AtomicUsize::new(0);
+    let workers = max_concurrent.clamp(1, items.len().max(1));
+
+    let mut results: Vec<_> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| scope.spawn(|| {
+                let mut mapped = vec![];
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    match items.get(i) {
+                        Some(item) => mapped.push((i, f(item))),
+                        None => break mapped,
+                    }
+                }
+            }))
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+            .collect()
+    });
+    results.sort_by_key(|(i, _)| *i);
+
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+fn fetch_transaction(
+    rpc_client: &RpcClient,
+    commitment: CommitmentConfig,
+    signature: &Signature,
+) -> Result<EncodedConfirmedTransactionWithStatusMeta, Error> {
+    Ok(rpc_client.get_transaction_with_config(signature, history_transaction_config(commitment))?)
+}
+
+/// Commitment used for history requests, which are not available at `processed`.
+fn history_commitment(commitment: CommitmentConfig) -> CommitmentConfig {
+    if commitment.is_at_least_confirmed() {
+        commitment
+    } else {
+        CommitmentConfig::confirmed()
+    }
+}
+
+/// History entries are fetched in base64, as expected by `history::parse_payment`.
+fn history_transaction_config(commitment: CommitmentConfig) -> RpcTransactionConfig {
+    RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Base64),
+        commitment: Some(commitment),
+    }
 }
 
 pub struct PreparedTransaction {
     pub message: Message,
-    pub fee: f64,
+    /// Estimated network fee, for the transaction as prepared (including any memo).
+    pub fee: Lamports,
+    /// The amount that will actually be transferred. For `SpendAmount::Some` this is the
+    /// requested amount, otherwise it is what is left after the fee and the reserve. SOL transfers
+    /// report lamports with 9 decimals, token transfers the mint's base units and decimals. For
+    /// Token-2022 mints with a transfer fee this is what leaves the sender; the recipient receives
+    /// it minus the mint's fee.
+    pub amount: TokenAmount,
+    /// Last block height at which the transaction can be processed. `None` when it was prepared
+    /// against a durable nonce, which doesn't expire, or a caller-supplied blockhash.
+    pub last_valid_block_height: Option<u64>,
+}
+
+impl PreparedTransaction {
+    /// Returns true if the transaction can no longer be processed and must be prepared again, e.g.
+    /// to warn the user before they sign it.
+    pub fn is_expired(&self, client: &PayClient) -> Result<bool, Error> {
+        let rpc_client = client.rpc_client();
+        let commitment = client.commitment();
+
+        if let Some(last_valid_block_height) = self.last_valid_block_height {
+            return Ok(rpc_client.get_block_height_with_commitment(commitment)? > last_valid_block_height);
+        }
+
+        let transaction = Transaction::new_unsigned(self.message.clone());
+        match uses_durable_nonce(&transaction).and_then(|ix| ix.accounts.first()) {
+            Some(index) => {
+                let nonce_account = self
+                    .message
+                    .account_keys
+                    .get(*index as usize)
+                    .ok_or_else(|| Error::InvalidTransaction("missing nonce account".to_string()))?;
+                let account = nonce_utils::get_account_with_commitment(rpc_client, nonce_account, commitment)?;
+                Ok(nonce_utils::data_from_account(&account)?.blockhash != self.message.recent_blockhash)
+            }
+            None => Ok(!is_blockhash_valid(rpc_client, &self.message.recent_blockhash, commitment)?),
+        }
+    }
+}
+
+/// Optional settings for `PayClient::create_transaction_with_config`.
+#[derive(Debug, Default, Clone)]
+pub struct TransactionConfig {
+    /// Blockhash to use instead of fetching the latest one. It must still be valid on the cluster,
+    /// or be the nonce stored in `nonce_account` when that is set.
+    pub blockhash: Option<Hash>,
+    /// Durable nonce account to use instead of a recent blockhash, so the prepared transaction
+    /// doesn't expire until the nonce is advanced. See `nonce`.
+    pub nonce_account: Option<Pubkey>,
+    /// Authority of `nonce_account`. Defaults to the sender. When set to another account, finish
+    /// the transaction with `PayClient::finish_transaction_with_nonce_authority`.
+    pub nonce_authority: Option<Pubkey>,
+    /// Account paying the network fee, e.g. a treasury sponsoring transfers of users who hold no
+    /// SOL. Defaults to the sender. When set, both accounts must sign; see `offline` for how to
+    /// collect the signatures separately.
+    pub fee_payer: Option<Pubkey>,
+    /// Attaches an SPL Memo, e.g. the deposit reference required by an exchange. Fails with
+    /// `Error::MemoTooLong`, before any request is made, if it doesn't fit in a single transaction.
+    pub memo: Option<String>,
+    /// Priority fee offered to land faster during congestion. It is included in
+    /// `PreparedTransaction::fee`.
+    pub priority_fee: PriorityFee,
+    /// Fails with `Error::RiskyRecipient` if `recipient::analyze_recipient_account` reports a
+    /// `Severity::Blocking` warning about the recipient.
+    pub refuse_risky_recipients: bool,
 }
 
 /// Prepares a transaction to send `amount` SOL to `recipient`'s wallet address. The transaction is
 /// initiated from `sender`'s address. The transaction can later be finished by
 /// `finish_transaction`.
-pub fn create_transaction(rpc_endpoint: &str, sender: &Pubkey, amount: f64, recipient: &Pubkey) -> Result<PreparedTransaction, String> {
-    let rpc_client = RpcClient::new(rpc_endpoint.to_string());
-    let spend_amount = SpendAmount::Some(sol_to_lamports(amount));
-    let memo = None;
+#[deprecated(note = "Use `PayClient::create_transaction`, which takes exact `Lamports`")]
+pub fn create_transaction(rpc_endpoint: &str, sender: &Pubkey, amount: f64, recipient: &Pubkey) -> Result<PreparedTransaction, Error> {
+    PayClient::new(Cluster::Custom(rpc_endpoint.to_string())).create_transaction(sender, Lamports(sol_to_lamports(amount)), recipient)
+}
 
-    let (message, fee) = prepare_transfer(&rpc_client, sender, spend_amount, recipient, memo).map_err(|e| format!("Error when preparing transfer: {}", e))?;
+/// Like `create_transaction`, but prepares the transaction against `nonce_account`, which the
+/// sender must be the authority of, so it stays valid until the nonce is advanced. See `nonce`.
+pub fn create_transaction_with_nonce(rpc_endpoint: &str, sender: &Pubkey, amount: Lamports, recipient: &Pubkey, nonce_account: &Pubkey) -> Result<PreparedTransaction, Error> {
+    let config = TransactionConfig {
+        nonce_account: Some(*nonce_account),
+        ..TransactionConfig::default()
+    };
 
-    Ok(PreparedTransaction {
-        message,
-        fee: lamports_to_sol(fee),
-    })
+    PayClient::new(Cluster::Custom(rpc_endpoint.to_string())).create_transaction_with_config(sender, SpendAmount::Some(amount), recipient, &config)
 }
 
 /// Signs and executes a transaction previously created by `create_transaction`.
-/// Returns the signature of the transaction if successful.
-pub fn finish_transaction(rpc_endpoint: &str, private_key: &Keypair, message: Message) -> Result<String, String> {
-    let rpc_client = RpcClient::new(rpc_endpoint.to_string());
-    let no_wait = true;
+/// Returns the base58 signature of the transaction as soon as the cluster accepted it; use
+/// `wait_for_confirmation` to learn whether it landed.
+pub fn finish_transaction(rpc_endpoint: &str, private_key: &Keypair, message: Message) -> Result<String, Error> {
+    PayClient::new(Cluster::Custom(rpc_endpoint.to_string())).finish_transaction(private_key, message)
+}
 
-    let res = sign_and_process_transaction(&rpc_client, private_key, no_wait, message).map_err(|e| format!("Error when finishing transaction: {}", e))?;
+/// Signs and executes a transaction previously created by `create_transaction_with_nonce`.
+pub fn finish_transaction_with_nonce(rpc_endpoint: &str, private_key: &Keypair, message: Message, nonce_account: &Pubkey) -> Result<String, Error> {
+    PayClient::new(Cluster::Custom(rpc_endpoint.to_string())).finish_transaction_with_nonce(private_key, message, nonce_account)
+}
 
-    Ok(format!("{:?}", res))
+/// Waits until the transaction `signature` reaches `commitment`. See
+/// `PayClient::wait_for_confirmation`.
+pub fn wait_for_confirmation<F>(rpc_endpoint: &str, signature: &Signature, commitment: CommitmentConfig, timeout: Duration, progress: F) -> Result<confirmation::TransactionConfirmation, Error>
+where
+    F: FnMut(&confirmation::ConfirmationProgress),
+{
+    PayClient::new(Cluster::Custom(rpc_endpoint.to_string())).wait_for_confirmation(signature, commitment, timeout, progress)
 }
 
-/// Converts a base58-encoded private key to its associated public key.
-pub fn private_key_to_pubkey(private_key: &str) -> String {
-    let keypair = Keypair::from_base58_string(private_key);
-    keypair.pubkey().to_string()
+/// Converts a private key, in any format accepted by `keys::import_key`, to its associated public
+/// key. Mnemonics are read without passphrase.
+pub fn private_key_to_pubkey(private_key: &str) -> Result<String, Error> {
+    let keypair = keys::import_key(private_key, "")?.keypair;
+    Ok(keypair.pubkey().to_string())
 }
 
 trait WithMemo {
@@ -125,38 +308,50 @@ impl WithMemo for Vec<Instruction> {
     }
 }
 
-fn get_fee_for_messages(
-    rpc_client: &RpcClient,
-    messages: &[&Message],
-) -> Result<u64, CliError> {
-    Ok(messages
-        .iter()
-        .map(|message| {
-            rpc_client.get_fee_for_message(message)
-        })
-        .collect::<Result<Vec<_>, _>>()?
-        .iter()
-        .sum())
-}
-
+/// How much SOL to transfer.
 #[derive(Debug, PartialEq, Clone, Copy)]
-enum SpendAmount {
+#[non_exhaustive]
+pub enum SpendAmount {
+    /// The sender's entire balance, minus the fee if the sender pays it. This closes the sender's
+    /// account.
     All,
-    Some(u64),
+    /// Exactly this amount.
+    Some(Lamports),
+    /// Like `All`, but keeps the minimum balance for rent exemption in the sender's account so it
+    /// stays open.
+    AllExceptRentExempt,
+    /// Like `All`, but keeps this amount in the sender's account.
+    AllExcept(Lamports),
 }
 
 impl Default for SpendAmount {
     fn default() -> Self {
-        Self::Some(u64::default())
+        Self::Some(Lamports::ZERO)
     }
 }
 
 impl SpendAmount {
-    pub fn new(amount: Option<u64>, sign_only: bool) -> Self {
-        match amount {
-            Some(lamports) => Self::Some(lamports),
-            None if !sign_only => Self::All,
-            _ => panic!("ALL amount not supported for sign-only operations"),
+    /// Returns the amount transferred out of `sender`'s `balance`, after `fee` when the sender also
+    /// pays it. `rent_exempt_minimum` is only used by `AllExceptRentExempt`.
+    ///
+    /// Fails with `Error::NothingToSend` when `All`, `AllExcept` or `AllExceptRentExempt` would
+    /// leave nothing to transfer, rather than paying a fee to move 0 lamports.
+    pub fn resolve(self, balance: Lamports, fee: Lamports, rent_exempt_minimum: Lamports, sender: &Pubkey, fee_payer: &Pubkey) -> Result<Lamports, Error> {
+        let reserve = match self {
+            SpendAmount::Some(lamports) => return Ok(lamports),
+            SpendAmount::All => Lamports::ZERO,
+            SpendAmount::AllExceptRentExempt => rent_exempt_minimum,
+            SpendAmount::AllExcept(reserve) => reserve,
+        };
+
+        let fee = if sender == fee_payer { fee } else { Lamports::ZERO };
+        // No balance can exceed a saturated requirement, so it still leaves nothing to send.
+        let required = reserve.checked_add(fee).unwrap_or(Lamports(u64::MAX));
+        let spend = balance.saturating_sub(required);
+        if spend > Lamports::ZERO {
+            Ok(spend)
+        } else {
+            Err(Error::NothingToSend(balance, required, *sender))
         }
     }
 }
@@ -166,306 +361,352 @@ struct SpendAndFee {
     fee: u64,
 }
 
-fn resolve_spend_message<F>(
-    rpc_client: &RpcClient,
-    amount: SpendAmount,
-    blockhash: Option<&Hash>,
+/// Checks that the sender can cover the spend, and the fee payer the fee. `fee_balance` is only
+/// used when they are different accounts.
+fn check_spend_balances(
+    cost: &SpendAndFee,
     from_balance: u64,
+    fee_balance: u64,
     from_pubkey: &Pubkey,
     fee_pubkey: &Pubkey,
-    build_message: F,
-) -> Result<(Message, SpendAndFee), CliError>
-where
-    F: Fn(u64) -> Message,
-{
-    let fee = match blockhash {
-        Some(blockhash) => {
-            let mut dummy_message = build_message(0);
-            dummy_message.recent_blockhash = *blockhash;
-            get_fee_for_messages(rpc_client, &[&dummy_message])?
+) -> Result<(), Error> {
+    let spend = cost.spend;
+    let fee = cost.fee;
+    if from_pubkey == fee_pubkey {
+        // An overflowing total can't be covered by any balance.
+        if from_balance == 0 || !spend.checked_add(fee).is_some_and(|total| from_balance >= total) {
+            return Err(Error::InsufficientFundsForSpendAndFee(
+                Lamports(spend),
+                Lamports(fee),
+                *from_pubkey,
+            ));
         }
-        None => 0, // Offline, cannot calculate fee
-    };
-
-    match amount {
-        SpendAmount::Some(lamports) => Ok((
-            build_message(lamports),
-            SpendAndFee {
-                spend: lamports,
-                fee,
-            },
-        )),
-        SpendAmount::All => {
-            let lamports = if from_pubkey == fee_pubkey {
-                from_balance.saturating_sub(fee)
-            } else {
-                from_balance
-            };
-            Ok((
-                build_message(lamports),
-                SpendAndFee {
-                    spend: lamports,
-                    fee,
-                },
-            ))
+    } else {
+        if from_balance < spend {
+            return Err(Error::InsufficientFundsForSpend(
+                Lamports(spend),
+                *from_pubkey,
+            ));
+        }
+        if fee_balance == 0 || fee_balance < fee {
+            return Err(Error::InsufficientFundsForFee(
+                Lamports(fee),
+                *fee_pubkey,
+            ));
         }
     }
+    Ok(())
 }
 
-/*fn check_account_for_balance_with_commitment(
-    rpc_client: &RpcClient,
-    account_pubkey: &Pubkey,
-    balance: u64,
-    commitment: CommitmentConfig,
-) -> solana_client::client_error::Result<bool> {
-    let lamports = rpc_client
-        .get_balance_with_commitment(account_pubkey, commitment)?
-        .value;
-    if lamports != 0 && lamports >= balance {
-        return Ok(true);
-    }
-    Ok(false)
-}*/
-
-#[derive(Debug, Error)]
-enum CliError {
-    #[error("Bad parameter: {0}")]
-    BadParameter(String),
-    #[error(transparent)]
-    ClientError(#[from] solana_client::client_error::ClientError),
-    #[error("Command not recognized: {0}")]
-    CommandNotRecognized(String),
-    #[error("Account {1} has insufficient funds for fee ({0} SOL)")]
-    InsufficientFundsForFee(f64, Pubkey),
-    #[error("Account {1} has insufficient funds for spend ({0} SOL)")]
-    InsufficientFundsForSpend(f64, Pubkey),
-    #[error("Account {2} has insufficient funds for spend ({0} SOL) + fee ({1} SOL)")]
-    InsufficientFundsForSpendAndFee(f64, f64, Pubkey),
-    /*#[error(transparent)]
-    InvalidNonce(nonce_utils::Error),*/
-    #[error("Dynamic program error: {0}")]
-    DynamicProgramError(String),
-    #[error("RPC request error: {0}")]
-    RpcRequestError(String),
-    #[error("Keypair file not found: {0}")]
-    KeypairFileNotFound(String),
-}
-
-impl From<Box<dyn std::error::Error>> for CliError {
-    fn from(error: Box<dyn std::error::Error>) -> Self {
-        CliError::DynamicProgramError(error.to_string())
-    }
+/// Balances a SOL transfer is checked against, fetched by either client.
+struct TransferBalances {
+    from_balance: u64,
+    /// Only used when the fee payer is not the sender.
+    fee_balance: u64,
+    /// `None` if the recipient doesn't exist yet.
+    recipient_balance: Option<u64>,
+    rent_exempt_minimum: u64,
 }
 
-/*impl From<nonce_utils::Error> for CliError {
-    fn from(error: nonce_utils::Error) -> Self {
-        match error {
-            nonce_utils::Error::Client(client_error) => Self::RpcRequestError(client_error),
-            _ => Self::InvalidNonce(error),
-        }
-    }
-}*/
-
-fn resolve_spend_tx_and_check_account_balances<F>(
-    rpc_client: &RpcClient,
-    sign_only: bool,
+/// Resolves `amount` and checks that the transfer can succeed: that both the spend and the fee are
+/// covered, and that no account is left below the rent exempt minimum.
+fn check_transfer(
     amount: SpendAmount,
-    blockhash: &Hash,
+    fee: u64,
+    balances: &TransferBalances,
     from_pubkey: &Pubkey,
     fee_pubkey: &Pubkey,
-    build_message: F,
-    commitment: CommitmentConfig,
-) -> Result<(Message, SpendAndFee), CliError>
-where
-    F: Fn(u64) -> Message,
-{
-    if sign_only {
-        unreachable!()
-        /*let (message, SpendAndFee { spend, fee: _ }) = resolve_spend_message(
-            rpc_client,
-            amount,
-            None,
-            0,
+    to: &Pubkey,
+) -> Result<SpendAndFee, Error> {
+    let spend = amount
+        .resolve(
+            Lamports(balances.from_balance),
+            Lamports(fee),
+            Lamports(balances.rent_exempt_minimum),
             from_pubkey,
             fee_pubkey,
-            build_message,
-        )?;
-        Ok((message, spend))*/
+        )?
+        .lamports();
+    let cost = SpendAndFee { spend, fee };
+
+    check_spend_balances(&cost, balances.from_balance, balances.fee_balance, from_pubkey, fee_pubkey)?;
+    check_rent_exemption(&cost, balances, from_pubkey, fee_pubkey, to)?;
+
+    Ok(cost)
+}
+
+/// Checks that a SOL transfer leaves every account it touches either rent exempt or empty, since
+/// the runtime rejects anything in between.
+fn check_rent_exemption(
+    cost: &SpendAndFee,
+    balances: &TransferBalances,
+    from_pubkey: &Pubkey,
+    fee_pubkey: &Pubkey,
+    to: &Pubkey,
+) -> Result<(), Error> {
+    let rent_exempt_minimum = Lamports(balances.rent_exempt_minimum);
+    // A self-transfer only costs the fee.
+    let spend = if from_pubkey == to { 0 } else { cost.spend };
+
+    let recipient_balance = balances.recipient_balance.unwrap_or_default();
+    if recipient_balance == 0 && spend > 0 && spend < balances.rent_exempt_minimum {
+        return Err(Error::RecipientNotRentExempt(Lamports(spend), rent_exempt_minimum, *to));
+    }
+
+    if from_pubkey == fee_pubkey {
+        check_remaining_balance(from_pubkey, Lamports(balances.from_balance), Lamports(spend.saturating_add(cost.fee)), rent_exempt_minimum)
     } else {
-        let from_balance = rpc_client
-            .get_balance_with_commitment(from_pubkey, commitment)?
-            .value;
-        let (message, cost) = resolve_spend_message(
-            rpc_client,
-            amount,
-            Some(blockhash),
-            from_balance,
-            from_pubkey,
-            fee_pubkey,
-            build_message,
-        )?;
-        let spend = cost.spend;
-        let fee = cost.fee;
-        if from_pubkey == fee_pubkey {
-            if from_balance == 0 || from_balance < spend + fee {
-                return Err(CliError::InsufficientFundsForSpendAndFee(
-                    lamports_to_sol(spend),
-                    lamports_to_sol(fee),
-                    *from_pubkey,
-                ));
-            }
-        } else {
-            unreachable!()
-            /*if from_balance < spend {
-                return Err(CliError::InsufficientFundsForSpend(
-                    lamports_to_sol(spend),
-                    *from_pubkey,
-                ));
+        check_remaining_balance(from_pubkey, Lamports(balances.from_balance), Lamports(spend), rent_exempt_minimum)?;
+        let received = if fee_pubkey == to { spend } else { 0 };
+        let fee_balance = Lamports(balances.fee_balance.saturating_add(received));
+        check_remaining_balance(fee_pubkey, fee_balance, Lamports(cost.fee), rent_exempt_minimum)
+    }
+}
+
+/// Checks that `account`, holding `balance`, is left either empty or rent exempt after paying
+/// `cost`, since the runtime rejects anything in between. Whether `balance` covers `cost` is not
+/// checked.
+pub fn check_remaining_balance(account: &Pubkey, balance: Lamports, cost: Lamports, rent_exempt_minimum: Lamports) -> Result<(), Error> {
+    let remaining = balance.saturating_sub(cost);
+    if remaining > Lamports::ZERO && remaining < rent_exempt_minimum {
+        return Err(Error::SenderNotRentExempt(remaining, rent_exempt_minimum, *account));
+    }
+
+    Ok(())
+}
+
+/// Returns the blockhash a new transaction is built against: the one stored in `nonce_account`, the
+/// caller-supplied `blockhash` after checking it against the cluster, or the latest one. The last
+/// valid block height is only known for the latest blockhash.
+fn resolve_blockhash(
+    rpc_client: &RpcClient,
+    commitment: CommitmentConfig,
+    blockhash: Option<Hash>,
+    nonce_account: Option<&Pubkey>,
+) -> Result<(Hash, Option<u64>), Error> {
+    if let Some(nonce_account) = nonce_account {
+        let account = nonce_utils::get_account_with_commitment(rpc_client, nonce_account, commitment)?;
+        return Ok((nonce::nonce_blockhash(&account, blockhash)?, None));
+    }
+
+    match blockhash {
+        Some(blockhash) => {
+            if !is_blockhash_valid(rpc_client, &blockhash, commitment)? {
+                return Err(expired_blockhash(&blockhash));
             }
-            if !check_account_for_balance_with_commitment(rpc_client, fee_pubkey, fee, commitment)?
-            {
-                return Err(CliError::InsufficientFundsForFee(
-                    lamports_to_sol(fee),
-                    *fee_pubkey,
-                ));
-            }*/
+            Ok((blockhash, None))
+        }
+        None => {
+            let (blockhash, last_valid_block_height) = get_latest_blockhash(rpc_client, commitment)?;
+            Ok((blockhash, Some(last_valid_block_height)))
         }
-        Ok((message, cost))
     }
 }
 
-type PrepareTransferResult = Result<(Message, u64), Box<dyn std::error::Error>>;
-type ProcessResult = Result<String, Box<dyn std::error::Error>>;
+fn expired_blockhash(blockhash: &Hash) -> Error {
+    Error::RpcRequestError(format!("Hash has expired {}", blockhash))
+}
+
+/// JSON RPC error code returned by nodes that don't know a method.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+fn is_method_not_found(error: &ClientError) -> bool {
+    matches!(
+        error.kind(),
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code: METHOD_NOT_FOUND, .. })
+    )
+}
+
+/// Returns the result of a request, or `None` if the node doesn't know its method, in which case
+/// both clients fall back to the deprecated method it replaced.
+fn unless_method_not_found<T>(result: Result<T, ClientError>) -> Result<Option<T>, Error> {
+    match result {
+        Err(e) if is_method_not_found(&e) => Ok(None),
+        result => Ok(Some(result?)),
+    }
+}
+
+/// Returns the latest blockhash and its last valid block height. Providers still running nodes
+/// older than v1.9 don't support `getLatestBlockhash`; they are asked with the deprecated
+/// `getFees` instead.
+#[allow(deprecated)]
+fn get_latest_blockhash(rpc_client: &RpcClient, commitment: CommitmentConfig) -> Result<(Hash, u64), Error> {
+    match unless_method_not_found(rpc_client.get_latest_blockhash_with_commitment(commitment))? {
+        Some(latest) => Ok(latest),
+        None => {
+            let fees = rpc_client.get_fees_with_commitment(commitment)?.value;
+            Ok((fees.blockhash, fees.last_valid_block_height))
+        }
+    }
+}
+
+/// Returns whether `blockhash` can still be used, falling back to the deprecated
+/// `getFeeCalculatorForBlockhash` like `get_latest_blockhash`.
+#[allow(deprecated)]
+fn is_blockhash_valid(rpc_client: &RpcClient, blockhash: &Hash, commitment: CommitmentConfig) -> Result<bool, Error> {
+    match unless_method_not_found(rpc_client.is_blockhash_valid(blockhash, commitment))? {
+        Some(is_valid) => Ok(is_valid),
+        None => Ok(rpc_client
+            .get_fee_calculator_for_blockhash_with_commitment(blockhash, commitment)?
+            .value
+            .is_some()),
+    }
+}
+
+/// Builds a message for `ixs`, prefixed with an advance nonce instruction when `nonce_account` is
+/// set.
+fn build_message_with_blockhash(
+    ixs: Vec<Instruction>,
+    fee_payer: &Pubkey,
+    nonce_account: Option<&Pubkey>,
+    nonce_authority: &Pubkey,
+    recent_blockhash: &Hash,
+) -> Message {
+    if let Some(nonce_account) = nonce_account {
+        let mut message = Message::new_with_nonce(
+            ixs,
+            Some(fee_payer),
+            nonce_account,
+            nonce_authority,
+        );
+        message.recent_blockhash = *recent_blockhash;
+        message
+    } else {
+        Message::new_with_blockhash(&ixs, Some(fee_payer), recent_blockhash)
+    }
+}
+
+/// What the message of a SOL transfer is built from, whatever the amount, shared by both clients.
+struct TransferTemplate<'a> {
+    sender: &'a Pubkey,
+    to: &'a Pubkey,
+    compute_budget_ixs: &'a [Instruction],
+    fee_payer: &'a Pubkey,
+    nonce_account: Option<&'a Pubkey>,
+    nonce_authority: &'a Pubkey,
+    memo: Option<&'a String>,
+    recent_blockhash: &'a Hash,
+}
+
+impl TransferTemplate<'_> {
+    fn message(&self, lamports: u64) -> Message {
+        let ixs = [self.compute_budget_ixs, &[system_instruction::transfer(self.sender, self.to, lamports)]]
+            .concat()
+            .with_memo(self.memo);
+
+        build_message_with_blockhash(ixs, self.fee_payer, self.nonce_account, self.nonce_authority, self.recent_blockhash)
+    }
+}
+
+type PrepareTransferResult = Result<(Message, SpendAndFee), Error>;
+type ProcessResult = Result<String, Error>;
 
 fn prepare_transfer(
     rpc_client: &RpcClient,
-    //config: &CliConfig,
-    //commitment: &CommitmentConfig,
-    sender: &Pubkey,
+    commitment: CommitmentConfig,
     amount: SpendAmount,
-    to: &Pubkey,
-    //sign_only: bool,
-    //blockhash_query: &BlockhashQuery,
-    //nonce_account: Option<&Pubkey>,
-    memo: Option<&String>,
-    //derived_address_seed: Option<String>,
-    //derived_address_program_id: Option<&Pubkey>,
+    template: &TransferTemplate,
 ) -> PrepareTransferResult {
-    let sign_only = false;
+    let from_balance = rpc_client
+        .get_balance_with_commitment(template.sender, commitment)?
+        .value;
+    let fee_balance = if template.sender == template.fee_payer {
+        from_balance
+    } else {
+        rpc_client
+            .get_balance_with_commitment(template.fee_payer, commitment)?
+            .value
+    };
+    let recipient_balance = rpc_client
+        .get_account_with_commitment(template.to, commitment)?
+        .value
+        .map(|account| account.lamports);
+    let balances = TransferBalances {
+        from_balance,
+        fee_balance,
+        recipient_balance,
+        rent_exempt_minimum: rpc_client.get_minimum_balance_for_rent_exemption(0)?,
+    };
+    let fee = compute_budget::get_fee_for_message(rpc_client, &template.message(0))?;
 
-    let from_pubkey = sender;
-    //let nonce_account = None;
-    let fee_payer = sender;
+    let cost = check_transfer(amount, fee, &balances, template.sender, template.fee_payer, template.to)?;
+    let message = template.message(cost.spend);
+    check_transaction_size(&message, template.memo)?;
 
-    let blockhash = None;
-    //let nonce_account = Some(sender.pubkey());
-    let nonce_account = None;
-    let blockhash_query = BlockhashQuery::new(blockhash, sign_only, nonce_account);
+    Ok((message, cost))
+}
 
-    let commitment = CommitmentConfig::finalized();
+/// Fails with `Error::MemoTooLong` if `config.memo` can't fit next to `ixs`, without making any
+/// request. Compute budget instructions and the blockhash are replaced by stand-ins of the same
+/// size, so transfers failing here would also fail `check_transaction_size` once prepared.
+fn check_memo_fits(ixs: Vec<Instruction>, fee_payer: &Pubkey, nonce_authority: &Pubkey, config: &TransactionConfig) -> Result<(), Error> {
+    if config.memo.is_none() {
+        return Ok(());
+    }
 
-    // TODO - get_recent_blockhash is deprecated on v1.9, but the replacement get_latest_blockhash
-    // doesn't work on mainnet RPC providers.
-    //let recent_blockhash = blockhash_query.get_blockhash(rpc_client, commitment)?;
-    let (recent_blockhash, _fee_calculator) = rpc_client.get_recent_blockhash()?;
+    let ixs = [config.priority_fee.placeholder_instructions(), ixs]
+        .concat()
+        .with_memo(config.memo.as_ref());
+    let message = build_message_with_blockhash(ixs, fee_payer, config.nonce_account.as_ref(), nonce_authority, &Hash::default());
 
-    /*let derived_parts = derived_address_seed.zip(derived_address_program_id);
-    let with_seed = if let Some((seed, program_id)) = derived_parts {
-        let base_pubkey = from_pubkey;
-        from_pubkey = Pubkey::create_with_seed(&base_pubkey, &seed, program_id)?;
-        Some((base_pubkey, seed, program_id, from_pubkey))
-    } else {
-        None
-    };*/
-
-    let build_message = |lamports| {
-        let ixs = /*if let Some((base_pubkey, seed, program_id, from_pubkey)) = with_seed.as_ref() {
-            vec![system_instruction::transfer_with_seed(
-                from_pubkey,
-                base_pubkey,
-                seed.clone(),
-                program_id,
-                to,
-                lamports,
-            )]
-            .with_memo(memo)
-        } else {*/
-            vec![system_instruction::transfer(&from_pubkey, to, lamports)].with_memo(memo)
-        /*}*/;
-
-        if let Some(nonce_account) = &nonce_account {
-            unreachable!()
-            /*Message::new_with_nonce(
-                ixs,
-                Some(&fee_payer.pubkey()),
-                nonce_account,
-                &nonce_authority.pubkey(),
-            )*/
-        } else {
-            Message::new_with_blockhash(&ixs, Some(&fee_payer), &recent_blockhash)
-        }
-    };
+    check_transaction_size(&message, config.memo.as_ref())
+}
 
-    let (message, cost) = resolve_spend_tx_and_check_account_balances(
-        rpc_client,
-        sign_only,
-        amount,
-        &recent_blockhash,
-        &from_pubkey,
-        &fee_payer,
-        build_message,
-        commitment,
-    )?;
+/// Fails if the signed transaction would not fit in a single packet, which can only happen because
+/// of a long memo.
+fn check_transaction_size(message: &Message, memo: Option<&String>) -> Result<(), Error> {
+    let size = bincode::serialized_size(&Transaction::new_unsigned(message.clone()))
+        .map_err(|e| Error::InvalidTransaction(e.to_string()))? as usize;
 
-    Ok((message, cost.fee))
+    if size > PACKET_DATA_SIZE {
+        let memo_len = memo.map(|memo| memo.len()).unwrap_or_default();
+        return Err(Error::MemoTooLong(memo_len, (memo_len + PACKET_DATA_SIZE).saturating_sub(size)));
+    }
+
+    Ok(())
 }
 
+/// Signs `message` with `sender`, and with `nonce_authority` when it is a different account, then
+/// sends it. Transactions prepared against `nonce_account` are first checked to still use its
+/// nonce and to name `nonce_authority`, or the sender when not given, as its authority.
 fn sign_and_process_transaction(
     rpc_client: &RpcClient,
+    commitment: CommitmentConfig,
     sender: &dyn Signer,
-    //sign_only: bool,
+    nonce_authority: Option<&dyn Signer>,
     //dump_transaction_message: bool,
     no_wait: bool,
-    //nonce_account: Option<&Pubkey>,
+    nonce_account: Option<&Pubkey>,
     message: Message,
 ) -> ProcessResult {
-    let sign_only = false;
-    let nonce_account: Option<&Pubkey> = None;
-
     let recent_blockhash = message.recent_blockhash.clone();
 
     let mut tx = Transaction::new_unsigned(message);
 
-    let signers = vec![sender];
-
-    if sign_only {
-        tx.try_partial_sign(&signers, recent_blockhash)?;
-        /*return_signers_with_config(
-            &tx,
-            &config.output_format,
-            &ReturnSignersConfig {
-                dump_transaction_message,
-            },
-        )*/
-        Ok("".to_string())
-    } else {
-        if let Some(nonce_account) = &nonce_account {
-            unreachable!()
-            /*let nonce_account = nonce_utils::get_account_with_commitment(
-                rpc_client,
-                nonce_account,
-                commitment,
-            )?;
-            check_nonce_account(&nonce_account, &nonce_authority.pubkey(), &recent_blockhash)?;*/
-        }
+    let (signers, nonce_authority) = nonce::transaction_signers(sender, nonce_authority);
 
-        tx.try_sign(&signers, recent_blockhash)?;
-        let signature = if no_wait {
-            rpc_client.send_transaction(&tx)
-        } else {
-            rpc_client.send_and_confirm_transaction_with_spinner(&tx)
-        }?;
-
-        Ok(signature.to_string())
+    if let Some(nonce_account) = &nonce_account {
+        let nonce_account = nonce_utils::get_account_with_commitment(
+            rpc_client,
+            nonce_account,
+            commitment,
+        )?;
+        nonce::check_nonce_account(&nonce_account, &nonce_authority.pubkey(), &recent_blockhash)?;
     }
+
+    tx.try_sign(&signers, recent_blockhash)?;
+    let signature = process_transaction(rpc_client, &tx, no_wait)?;
+
+    Ok(signature.to_string())
+}
+
+/// Broadcasts a fully signed transaction.
+fn process_transaction(rpc_client: &RpcClient, tx: &Transaction, no_wait: bool) -> Result<Signature, Error> {
+    let signature = if no_wait {
+        rpc_client.send_transaction(tx)
+    } else {
+        rpc_client.send_and_confirm_transaction_with_spinner(tx)
+    }?;
+
+    Ok(signature)
 }
\ No newline at end of file
@@ -0,0 +1,382 @@
+//! Async variant of the API, built on the nonblocking RPC client. Requires the `async` feature.
+//!
+//! `nonblocking::PayClient` mirrors `crate::PayClient` for balances, transfers and history, so
+//! apps running on an async runtime don't need `spawn_blocking`. Its futures must be polled from
+//! within a Tokio runtime, as required by `solana_client`. The blocking client and the free
+//! functions remain available and behave the same.
+
+use crate::{
+    check_memo_fits, check_transaction_size, check_transfer, expired_blockhash,
+    compute_budget::{split_priority_fee, PriorityFee},
+    confirmation::{check_confirmation, ConfirmationProgress, TransactionConfirmation, POLL_INTERVAL},
+    history::{parse_payment, HistoryEntry, PaymentRecord},
+    history_commitment, history_transaction_config, nonce, offline,
+    recipient::{analyze_recipient_account, check_recipient_warnings, RecipientWarning},
+    signatures_for_address_config, unless_method_not_found, Cluster, Error, Lamports, PayClientConfig,
+    PreparedTransaction, SpendAmount, TransactionConfig, TransferBalances, TransferTemplate,
+};
+use futures::stream::{self, StreamExt};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{
+    account::Account,
+    commitment_config::CommitmentConfig,
+    hash::Hash,
+    instruction::Instruction,
+    message::Message,
+    signature::{Keypair, Signature, Signer},
+    system_instruction,
+    transaction::Transaction,
+};
+use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Async counterpart of `crate::PayClient`.
+pub struct PayClient {
+    rpc_client: RpcClient,
+    cluster: Cluster,
+    config: PayClientConfig,
+}
+
+impl PayClient {
+    pub fn new(cluster: Cluster) -> Self {
+        Self::new_with_config(cluster, PayClientConfig::default())
+    }
+
+    pub fn new_with_config(cluster: Cluster, config: PayClientConfig) -> Self {
+        let rpc_client = RpcClient::new_with_timeout_and_commitment(
+            cluster.url().to_string(),
+            config.timeout,
+            config.commitment,
+        );
+
+        Self {
+            rpc_client,
+            cluster,
+            config,
+        }
+    }
+
+    /// Returns the underlying RPC client for requests not covered by `PayClient`.
+    pub fn rpc_client(&self) -> &RpcClient {
+        &self.rpc_client
+    }
+
+    pub fn cluster(&self) -> &Cluster {
+        &self.cluster
+    }
+
+    pub fn commitment(&self) -> CommitmentConfig {
+        self.config.commitment
+    }
+
+    /// Returns the SOL balance of the given wallet address.
+    pub async fn get_balance(&self, base58_pubkey: &str) -> Result<Lamports, Error> {
+        let pubkey = Pubkey::from_str(base58_pubkey)?;
+        let balance = self
+            .rpc_client
+            .get_balance_with_commitment(&pubkey, self.commitment())
+            .await?
+            .value;
+
+        Ok(Lamports(balance))
+    }
+
+    /// See `crate::PayClient::create_transaction`.
+    pub async fn create_transaction(&self, sender: &Pubkey, amount: Lamports, recipient: &Pubkey) -> Result<PreparedTransaction, Error> {
+        let amount = SpendAmount::Some(amount);
+
+        self.create_transaction_with_config(sender, amount, recipient, &TransactionConfig::default()).await
+    }
+
+    /// See `crate::PayClient::create_transaction_with_config`.
+    pub async fn create_transaction_with_config(&self, sender: &Pubkey, amount: SpendAmount, recipient: &Pubkey, config: &TransactionConfig) -> Result<PreparedTransaction, Error> {
+        let commitment = self.commitment();
+        let fee_payer = config.fee_payer.as_ref().unwrap_or(sender);
+        let nonce_account = config.nonce_account.as_ref();
+        let nonce_authority = config.nonce_authority.as_ref().unwrap_or(sender);
+
+        check_memo_fits(vec![system_instruction::transfer(sender, recipient, 0)], fee_payer, nonce_authority, config)?;
+        if config.refuse_risky_recipients {
+            check_recipient_warnings(recipient, self.analyze_recipient(sender, recipient).await?)?;
+        }
+
+        let (recent_blockhash, last_valid_block_height) = self.resolve_blockhash(config.blockhash, nonce_account).await?;
+        let compute_budget_ixs = self.compute_budget_instructions(&config.priority_fee, &[*fee_payer, *sender, *recipient]).await?;
+        let template = TransferTemplate {
+            sender,
+            to: recipient,
+            compute_budget_ixs: &compute_budget_ixs,
+            fee_payer,
+            nonce_account,
+            nonce_authority,
+            memo: config.memo.as_ref(),
+            recent_blockhash: &recent_blockhash,
+        };
+
+        let from_balance = self
+            .rpc_client
+            .get_balance_with_commitment(sender, commitment)
+            .await?
+            .value;
+        let fee_balance = if fee_payer == sender {
+            from_balance
+        } else {
+            self.rpc_client
+                .get_balance_with_commitment(fee_payer, commitment)
+                .await?
+                .value
+        };
+        let recipient_balance = self
+            .rpc_client
+            .get_account_with_commitment(recipient, commitment)
+            .await?
+            .value
+            .map(|account| account.lamports);
+        let balances = TransferBalances {
+            from_balance,
+            fee_balance,
+            recipient_balance,
+            rent_exempt_minimum: self.rpc_client.get_minimum_balance_for_rent_exemption(0).await?,
+        };
+        let (base_message, priority_fee) = split_priority_fee(&template.message(0));
+        let fee = self.rpc_client.get_fee_for_message(&base_message).await?.saturating_add(priority_fee);
+
+        let cost = check_transfer(amount, fee, &balances, sender, fee_payer, recipient)?;
+        let message = template.message(cost.spend);
+        check_transaction_size(&message, config.memo.as_ref())?;
+
+        Ok(PreparedTransaction {
+            message,
+            fee: Lamports(cost.fee),
+            amount: Lamports(cost.spend).into(),
+            last_valid_block_height,
+        })
+    }
+
+    /// See `crate::PayClient::analyze_recipient`.
+    pub async fn analyze_recipient(&self, sender: &Pubkey, recipient: &Pubkey) -> Result<Vec<RecipientWarning>, Error> {
+        let account = self
+            .rpc_client
+            .get_account_with_commitment(recipient, self.commitment())
+            .await?
+            .value;
+
+        Ok(analyze_recipient_account(sender, recipient, account.as_ref()))
+    }
+
+    /// See `crate::PayClient::finish_transaction`.
+    pub async fn finish_transaction(&self, private_key: &Keypair, message: Message) -> Result<String, Error> {
+        self.sign_and_send(private_key, None, None, message).await
+    }
+
+    /// See `crate::PayClient::finish_transaction_with_nonce`.
+    pub async fn finish_transaction_with_nonce(&self, private_key: &Keypair, message: Message, nonce_account: &Pubkey) -> Result<String, Error> {
+        self.sign_and_send(private_key, None, Some(nonce_account), message).await
+    }
+
+    /// See `crate::PayClient::finish_transaction_with_nonce_authority`.
+    pub async fn finish_transaction_with_nonce_authority(&self, private_key: &Keypair, nonce_authority: &Keypair, message: Message, nonce_account: &Pubkey) -> Result<String, Error> {
+        self.sign_and_send(private_key, Some(nonce_authority), Some(nonce_account), message).await
+    }
+
+    async fn sign_and_send(&self, private_key: &Keypair, nonce_authority: Option<&Keypair>, nonce_account: Option<&Pubkey>, message: Message) -> Result<String, Error> {
+        let recent_blockhash = message.recent_blockhash;
+        let (signers, nonce_authority) = nonce::transaction_signers(private_key, nonce_authority);
+
+        if let Some(nonce_account) = nonce_account {
+            let account = self.get_nonce_account_data(nonce_account).await?;
+            nonce::check_nonce_account(&account, &nonce_authority.pubkey(), &recent_blockhash)?;
+        }
+
+        let mut tx = Transaction::new_unsigned(message);
+        tx.try_sign(&signers, recent_blockhash)?;
+        let signature = self.rpc_client.send_transaction(&tx).await?;
+
+        Ok(signature.to_string())
+    }
+
+    /// See `crate::PayClient::wait_for_confirmation`.
+    pub async fn wait_for_confirmation<F>(&self, signature: &Signature, commitment: CommitmentConfig, timeout: Duration, mut progress: F) -> Result<TransactionConfirmation, Error>
+    where
+        F: FnMut(&ConfirmationProgress),
+    {
+        let start = Instant::now();
+
+        loop {
+            // Transient RPC errors are retried until the timeout.
+            if let Ok(response) = self.rpc_client.get_signature_statuses(&[*signature]).await {
+                let status = response.value.into_iter().next().flatten();
+                match check_confirmation(signature, status, commitment) {
+                    Ok(confirmation) => return Ok(confirmation),
+                    Err(pending) => progress(&pending),
+                }
+            }
+
+            if start.elapsed() >= timeout {
+                return Err(Error::ConfirmationTimeout(*signature));
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// See `crate::PayClient::send_signed_transaction`.
+    pub async fn send_signed_transaction(&self, encoded: &str) -> Result<Signature, Error> {
+        let transaction = offline::decode_transaction(encoded)?;
+
+        if !transaction.is_signed() {
+            return Err(Error::InvalidTransaction("transaction is missing signatures".to_string()));
+        }
+        transaction
+            .verify()
+            .map_err(|e| Error::InvalidTransaction(e.to_string()))?;
+
+        Ok(self.rpc_client.send_transaction(&transaction).await?)
+    }
+
+    /// See `crate::PayClient::process_transaction_history`.
+    pub async fn process_transaction_history(
+        &self,
+        address: &Pubkey,
+        before: Option<Signature>,
+        until: Option<Signature>,
+        limit: usize,
+    ) -> Result<Vec<EncodedConfirmedTransactionWithStatusMeta>, Error> {
+        self.get_transaction_history(address, before, until, limit)
+            .await?
+            .into_iter()
+            .map(|entry| entry.transaction)
+            .collect()
+    }
+
+    /// See `crate::PayClient::get_transaction_history`.
+    pub async fn get_transaction_history(
+        &self,
+        address: &Pubkey,
+        before: Option<Signature>,
+        until: Option<Signature>,
+        limit: usize,
+    ) -> Result<Vec<HistoryEntry>, Error> {
+        let results = self
+            .rpc_client
+            .get_signatures_for_address_with_config(
+                address,
+                signatures_for_address_config(self.history_commitment(), before, until, limit),
+            )
+            .await?;
+        let signatures = results
+            .into_iter()
+            .map(|result| result.signature.parse::<Signature>())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // `buffered` keeps the order of `signatures` while running the requests concurrently.
+        let transactions: Vec<_> = stream::iter(&signatures)
+            .map(|signature| self.fetch_transaction(signature))
+            .buffered(self.config.max_concurrent_requests.max(1))
+            .collect()
+            .await;
+
+        Ok(signatures
+            .into_iter()
+            .zip(transactions)
+            .map(|(signature, transaction)| HistoryEntry {
+                signature,
+                transaction,
+            })
+            .collect())
+    }
+
+    /// See `crate::PayClient::get_payment_history`.
+    pub async fn get_payment_history(
+        &self,
+        address: &Pubkey,
+        before: Option<Signature>,
+        until: Option<Signature>,
+        limit: usize,
+    ) -> Result<Vec<PaymentRecord>, Error> {
+        self.process_transaction_history(address, before, until, limit)
+            .await?
+            .iter()
+            .map(|entry| parse_payment(address, entry))
+            .collect()
+    }
+
+    async fn fetch_transaction(&self, signature: &Signature) -> Result<EncodedConfirmedTransactionWithStatusMeta, Error> {
+        Ok(self
+            .rpc_client
+            .get_transaction_with_config(signature, history_transaction_config(self.history_commitment()))
+            .await?)
+    }
+
+    fn history_commitment(&self) -> CommitmentConfig {
+        history_commitment(self.commitment())
+    }
+
+    /// Async counterpart of `crate::resolve_blockhash`.
+    async fn resolve_blockhash(&self, blockhash: Option<Hash>, nonce_account: Option<&Pubkey>) -> Result<(Hash, Option<u64>), Error> {
+        if let Some(nonce_account) = nonce_account {
+            let account = self.get_nonce_account_data(nonce_account).await?;
+            return Ok((nonce::nonce_blockhash(&account, blockhash)?, None));
+        }
+
+        match blockhash {
+            Some(blockhash) => {
+                if !self.is_blockhash_valid(&blockhash).await? {
+                    return Err(expired_blockhash(&blockhash));
+                }
+                Ok((blockhash, None))
+            }
+            None => {
+                let (blockhash, last_valid_block_height) = self.get_latest_blockhash().await?;
+                Ok((blockhash, Some(last_valid_block_height)))
+            }
+        }
+    }
+
+    /// Async counterpart of `PriorityFee::instructions`.
+    async fn compute_budget_instructions(&self, priority_fee: &PriorityFee, writable_accounts: &[Pubkey]) -> Result<Vec<Instruction>, Error> {
+        let fees_response: Option<serde_json::Value> = match priority_fee.fees_request(writable_accounts) {
+            Some((request, params)) => Some(self.rpc_client.send(request, params).await?),
+            None => None,
+        };
+
+        priority_fee.instructions_from(fees_response.as_ref())
+    }
+
+    /// Async counterpart of `crate::get_latest_blockhash`.
+    #[allow(deprecated)]
+    async fn get_latest_blockhash(&self) -> Result<(Hash, u64), Error> {
+        let commitment = self.commitment();
+        match unless_method_not_found(self.rpc_client.get_latest_blockhash_with_commitment(commitment).await)? {
+            Some(latest) => Ok(latest),
+            None => {
+                let fees = self.rpc_client.get_fees_with_commitment(commitment).await?.value;
+                Ok((fees.blockhash, fees.last_valid_block_height))
+            }
+        }
+    }
+
+    /// Async counterpart of `crate::is_blockhash_valid`.
+    #[allow(deprecated)]
+    async fn is_blockhash_valid(&self, blockhash: &Hash) -> Result<bool, Error> {
+        let commitment = self.commitment();
+        match unless_method_not_found(self.rpc_client.is_blockhash_valid(blockhash, commitment).await)? {
+            Some(is_valid) => Ok(is_valid),
+            None => Ok(self
+                .rpc_client
+                .get_fee_calculator_for_blockhash_with_commitment(blockhash, commitment)
+                .await?
+                .value
+                .is_some()),
+        }
+    }
+
+    async fn get_nonce_account_data(&self, nonce_account: &Pubkey) -> Result<Account, Error> {
+        self.rpc_client
+            .get_account_with_commitment(nonce_account, self.commitment())
+            .await?
+            .value
+            .ok_or_else(|| Error::RpcRequestError(format!("AccountNotFound: pubkey={}", nonce_account)))
+    }
+}
@@ -0,0 +1,159 @@
+//! Durable transaction nonces.
+//!
+//! A prepared transaction normally references a recent blockhash and expires after roughly two
+//! minutes. Preparing it against a nonce account instead keeps it valid until the nonce is
+//! advanced, which happens automatically when the transaction is processed.
+
+use crate::{get_latest_blockhash, Error, Lamports, PayClient};
+use solana_client::nonce_utils;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{
+    account::Account,
+    hash::Hash,
+    instruction::Instruction,
+    message::Message,
+    nonce::State,
+    signature::{Keypair, Signature, Signer},
+    system_instruction,
+    transaction::Transaction,
+};
+
+/// The current state of a nonce account.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NonceAccount {
+    pub address: Pubkey,
+    /// The account allowed to advance, authorize and withdraw from the nonce account.
+    pub authority: Pubkey,
+    /// The stored nonce, used in place of a recent blockhash.
+    pub nonce: Hash,
+    pub lamports_per_signature: u64,
+    /// Balance of the nonce account.
+    pub balance: Lamports,
+}
+
+impl PayClient {
+    /// Creates and initializes the nonce account `nonce_account`, funded by `payer` with the
+    /// minimum balance for rent exemption. `nonce_authority` will be allowed to use it.
+    pub fn create_nonce_account(&self, payer: &Keypair, nonce_account: &Keypair, nonce_authority: &Pubkey) -> Result<Signature, Error> {
+        let rent = self
+            .rpc_client()
+            .get_minimum_balance_for_rent_exemption(State::size())?;
+        let ixs = system_instruction::create_nonce_account(
+            &payer.pubkey(),
+            &nonce_account.pubkey(),
+            nonce_authority,
+            rent,
+        );
+
+        self.send_nonce_instructions(ixs, payer, &[payer, nonce_account], rent)
+    }
+
+    /// Returns the state of an initialized nonce account.
+    pub fn get_nonce_account(&self, nonce_account: &Pubkey) -> Result<NonceAccount, Error> {
+        let account = nonce_utils::get_account_with_commitment(self.rpc_client(), nonce_account, self.commitment())?;
+        let data = nonce_utils::data_from_account(&account)?;
+
+        Ok(NonceAccount {
+            address: *nonce_account,
+            authority: data.authority,
+            nonce: data.blockhash,
+            lamports_per_signature: data.fee_calculator.lamports_per_signature,
+            balance: Lamports(account.lamports),
+        })
+    }
+
+    /// Advances the stored nonce, invalidating every transaction prepared against the old one.
+    /// `fee_payer` may be `nonce_authority`, or another account so the authority needs no SOL.
+    pub fn advance_nonce_account(&self, fee_payer: &Keypair, nonce_authority: &Keypair, nonce_account: &Pubkey) -> Result<Signature, Error> {
+        let ix = system_instruction::advance_nonce_account(nonce_account, &nonce_authority.pubkey());
+        let (signers, _) = transaction_signers(fee_payer, Some(nonce_authority));
+
+        self.send_nonce_instructions(vec![ix], fee_payer, &signers, 0)
+    }
+
+    /// Hands control over `nonce_account` to `new_authority`. `fee_payer` may be
+    /// `nonce_authority`.
+    pub fn authorize_nonce_account(&self, fee_payer: &Keypair, nonce_authority: &Keypair, nonce_account: &Pubkey, new_authority: &Pubkey) -> Result<Signature, Error> {
+        let ix = system_instruction::authorize_nonce_account(nonce_account, &nonce_authority.pubkey(), new_authority);
+        let (signers, _) = transaction_signers(fee_payer, Some(nonce_authority));
+
+        self.send_nonce_instructions(vec![ix], fee_payer, &signers, 0)
+    }
+
+    /// Withdraws `amount` from `nonce_account` to `recipient`. Withdrawing the whole balance
+    /// closes the nonce account. `fee_payer` may be `nonce_authority`.
+    pub fn withdraw_from_nonce_account(
+        &self,
+        fee_payer: &Keypair,
+        nonce_authority: &Keypair,
+        nonce_account: &Pubkey,
+        recipient: &Pubkey,
+        amount: Lamports,
+    ) -> Result<Signature, Error> {
+        let ix = system_instruction::withdraw_nonce_account(
+            nonce_account,
+            &nonce_authority.pubkey(),
+            recipient,
+            amount.lamports(),
+        );
+        let (signers, _) = transaction_signers(fee_payer, Some(nonce_authority));
+
+        self.send_nonce_instructions(vec![ix], fee_payer, &signers, 0)
+    }
+
+    /// Signs `ixs`, checks that `fee_payer` can cover `spend` plus the fee and waits until the
+    /// transaction is confirmed.
+    fn send_nonce_instructions(&self, ixs: Vec<Instruction>, fee_payer: &Keypair, signers: &[&Keypair], spend: u64) -> Result<Signature, Error> {
+        let rpc_client = self.rpc_client();
+        let (recent_blockhash, _last_valid_block_height) = get_latest_blockhash(rpc_client, self.commitment())?;
+        let message = Message::new_with_blockhash(&ixs, Some(&fee_payer.pubkey()), &recent_blockhash);
+
+        let fee = rpc_client.get_fee_for_message(&message)?;
+        let balance = rpc_client
+            .get_balance_with_commitment(&fee_payer.pubkey(), self.commitment())?
+            .value;
+        if !spend.checked_add(fee).is_some_and(|total| balance >= total) {
+            return Err(Error::InsufficientFundsForSpendAndFee(Lamports(spend), Lamports(fee), fee_payer.pubkey()));
+        }
+
+        let mut tx = Transaction::new_unsigned(message);
+        tx.try_sign(signers, recent_blockhash)?;
+
+        Ok(rpc_client.send_and_confirm_transaction(&tx)?)
+    }
+}
+
+/// Returns the signers of a transaction sent by `sender`, and the one that must be the nonce
+/// authority: `nonce_authority` if given, otherwise `sender`. A nonce authority that is also the
+/// sender only signs once.
+pub fn transaction_signers<'a, S: Signer + ?Sized>(sender: &'a S, nonce_authority: Option<&'a S>) -> (Vec<&'a S>, &'a S) {
+    match nonce_authority {
+        Some(nonce_authority) if nonce_authority.pubkey() != sender.pubkey() => (vec![sender, nonce_authority], nonce_authority),
+        _ => (vec![sender], sender),
+    }
+}
+
+/// Returns the nonce stored in the nonce account `account`, which a caller-supplied `blockhash`
+/// must match.
+pub fn nonce_blockhash(account: &Account, blockhash: Option<Hash>) -> Result<Hash, Error> {
+    let nonce = nonce_utils::data_from_account(account)?.blockhash;
+    match blockhash {
+        Some(blockhash) if blockhash != nonce => Err(nonce_utils::Error::InvalidHash.into()),
+        _ => Ok(nonce),
+    }
+}
+
+/// Checks that `nonce_account` is initialized, is controlled by `nonce_authority` and still
+/// stores `nonce_hash`.
+pub fn check_nonce_account(nonce_account: &Account, nonce_authority: &Pubkey, nonce_hash: &Hash) -> Result<(), Error> {
+    let state = nonce_utils::state_from_account(nonce_account)?;
+    let data = nonce_utils::data_from_state(&state)?;
+
+    if &data.blockhash != nonce_hash {
+        Err(nonce_utils::Error::InvalidHash.into())
+    } else if nonce_authority != &data.authority {
+        Err(nonce_utils::Error::InvalidAuthority.into())
+    } else {
+        Ok(())
+    }
+}
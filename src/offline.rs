@@ -0,0 +1,84 @@
+//! Air-gapped signing of prepared transactions.
+//!
+//! The flow is split across an online and an offline device:
+//!
+//! 1. Online: `PayClient::create_transaction_with_blockhash` prepares a transfer against a
+//!    blockhash chosen by the caller, and `encode_message` turns it into an unsigned transaction.
+//! 2. Offline: `sign_transaction` adds the signature of the given keypair. No network access is
+//!    needed, the blockhash is taken from the transaction itself.
+//! 3. Online: `PayClient::send_signed_transaction` checks the signatures and broadcasts it.
+//!
+//! # Exchange format
+//!
+//! Transactions are exchanged between devices as a single string: the standard base64 encoding
+//! (with padding) of the bincode-serialized `Transaction`. This is the same wire format accepted by
+//! the `sendTransaction` RPC method with `"encoding": "base64"`. Signatures that have not been
+//! provided yet are all zeroes.
+//!
+//! The blockhash of a prepared transaction expires after roughly two minutes, so all three steps
+//! must complete within that window unless it was prepared against a durable nonce.
+//!
+//! # Sponsored transfers
+//!
+//! The same format is used when a transaction needs more than one signature, e.g. when
+//! `TransactionConfig::fee_payer` is set. Each party calls `sign_transaction` with its own key in
+//! any order, `missing_signers` tells who still has to sign, and the last one to hold the
+//! transaction sends it with `PayClient::send_signed_transaction`.
+
+use crate::Error;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{
+    message::Message,
+    signature::{Keypair, Signature},
+    transaction::Transaction,
+};
+
+/// Encodes `message` as an unsigned transaction in the exchange format.
+pub fn encode_message(message: Message) -> Result<String, Error> {
+    encode_transaction(&Transaction::new_unsigned(message))
+}
+
+/// Encodes a (possibly partially) signed transaction in the exchange format.
+pub fn encode_transaction(transaction: &Transaction) -> Result<String, Error> {
+    let bytes = bincode::serialize(transaction).map_err(|e| Error::InvalidTransaction(e.to_string()))?;
+
+    Ok(base64::encode(bytes))
+}
+
+/// Decodes a transaction from the exchange format.
+pub fn decode_transaction(encoded: &str) -> Result<Transaction, Error> {
+    let bytes = base64::decode(encoded.trim()).map_err(|e| Error::InvalidTransaction(e.to_string()))?;
+    let transaction: Transaction = bincode::deserialize(&bytes).map_err(|e| Error::InvalidTransaction(e.to_string()))?;
+
+    if transaction.signatures.len() != transaction.message.header.num_required_signatures as usize {
+        return Err(Error::InvalidTransaction("signature count does not match message header".to_string()));
+    }
+
+    Ok(transaction)
+}
+
+/// Signs an encoded transaction with `private_key` without contacting the network, returning the
+/// re-encoded transaction. Fails if `private_key` is not a required signer of the transaction.
+pub fn sign_transaction(private_key: &Keypair, encoded: &str) -> Result<String, Error> {
+    let mut transaction = decode_transaction(encoded)?;
+    let recent_blockhash = transaction.message.recent_blockhash;
+
+    transaction.try_partial_sign(&[private_key], recent_blockhash)?;
+
+    encode_transaction(&transaction)
+}
+
+
+/// Returns the accounts whose signature is still missing from an encoded transaction, in the order
+/// they appear in the message.
+pub fn missing_signers(encoded: &str) -> Result<Vec<Pubkey>, Error> {
+    let transaction = decode_transaction(encoded)?;
+
+    Ok(transaction
+        .signatures
+        .iter()
+        .zip(transaction.message.account_keys.iter())
+        .filter(|(signature, _)| **signature == Signature::default())
+        .map(|(_, pubkey)| *pubkey)
+        .collect())
+}
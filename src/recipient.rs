@@ -0,0 +1,127 @@
+//! Safety analysis of recipient addresses.
+//!
+//! Any `Pubkey` is a valid recipient, but SOL sent to some of them is lost or stuck. The warnings
+//! returned here can be displayed before the user confirms a payment, and
+//! `TransactionConfig::refuse_risky_recipients` turns `Severity::Blocking` ones into errors.
+
+use crate::token::{is_token_program, token_account_kind, TokenAccountKind};
+use crate::{Error, PayClient};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{account::Account, incinerator, system_program};
+use std::fmt;
+
+/// How seriously a `RecipientWarning` should be taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Worth showing, but usually intended.
+    Info,
+    /// Possibly intended, ask the user to double check.
+    Warning,
+    /// Funds would almost certainly be lost.
+    Blocking,
+}
+
+/// A reason to double check a recipient.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RecipientWarning {
+    /// The sender is paying itself.
+    SelfTransfer,
+    /// The address is off the ed25519 curve, e.g. a program derived address, so no one holds a
+    /// private key for it. Only the program that derived it can move the funds.
+    OffCurve,
+    /// The address is an executable program account.
+    Executable,
+    /// The address is a token mint rather than a wallet.
+    TokenMint,
+    /// The address is a token account rather than a wallet; `owner` is probably the intended
+    /// recipient.
+    TokenAccount { mint: Pubkey, owner: Pubkey },
+    /// The address is a known burn address, such as the incinerator.
+    BurnAddress,
+}
+
+impl RecipientWarning {
+    pub fn severity(&self) -> Severity {
+        match self {
+            RecipientWarning::SelfTransfer => Severity::Info,
+            RecipientWarning::OffCurve => Severity::Warning,
+            RecipientWarning::Executable
+            | RecipientWarning::TokenMint
+            | RecipientWarning::TokenAccount { .. }
+            | RecipientWarning::BurnAddress => Severity::Blocking,
+        }
+    }
+}
+
+impl fmt::Display for RecipientWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecipientWarning::SelfTransfer => write!(f, "the recipient is the sender"),
+            RecipientWarning::OffCurve => write!(f, "the recipient is a program derived address that no one can sign for"),
+            RecipientWarning::Executable => write!(f, "the recipient is a program"),
+            RecipientWarning::TokenMint => write!(f, "the recipient is a token mint"),
+            RecipientWarning::TokenAccount { owner, .. } => write!(f, "the recipient is a token account owned by {}", owner),
+            RecipientWarning::BurnAddress => write!(f, "the recipient is a burn address"),
+        }
+    }
+}
+
+/// Returns true for addresses SOL can be sent to but never recovered from.
+pub fn is_burn_address(address: &Pubkey) -> bool {
+    incinerator::check_id(address) || system_program::check_id(address)
+}
+
+impl PayClient {
+    /// Returns the warnings about sending to `recipient` from `sender`, most severe first.
+    pub fn analyze_recipient(&self, sender: &Pubkey, recipient: &Pubkey) -> Result<Vec<RecipientWarning>, Error> {
+        let account = self
+            .rpc_client()
+            .get_account_with_commitment(recipient, self.commitment())?
+            .value;
+
+        Ok(analyze_recipient_account(sender, recipient, account.as_ref()))
+    }
+
+    /// Fails with the most severe warning about `recipient` if it is `Severity::Blocking`.
+    pub(crate) fn check_recipient(&self, sender: &Pubkey, recipient: &Pubkey) -> Result<(), Error> {
+        check_recipient_warnings(recipient, self.analyze_recipient(sender, recipient)?)
+    }
+}
+
+/// Like `PayClient::analyze_recipient`, for an already fetched `account`, which is `None` if the
+/// recipient doesn't exist yet.
+pub fn analyze_recipient_account(sender: &Pubkey, recipient: &Pubkey, account: Option<&Account>) -> Vec<RecipientWarning> {
+    let mut warnings = vec![];
+
+    if sender == recipient {
+        warnings.push(RecipientWarning::SelfTransfer);
+    }
+    if is_burn_address(recipient) {
+        warnings.push(RecipientWarning::BurnAddress);
+    } else if !recipient.is_on_curve() {
+        warnings.push(RecipientWarning::OffCurve);
+    }
+
+    if let Some(account) = account {
+        if account.executable {
+            warnings.push(RecipientWarning::Executable);
+        } else if is_token_program(&account.owner) {
+            match token_account_kind(&account.data) {
+                Some(TokenAccountKind::Mint) => warnings.push(RecipientWarning::TokenMint),
+                Some(TokenAccountKind::Account { mint, owner }) => warnings.push(RecipientWarning::TokenAccount { mint, owner }),
+                None => {}
+            }
+        }
+    }
+
+    warnings.sort_by_key(|warning| std::cmp::Reverse(warning.severity()));
+    warnings
+}
+
+pub(crate) fn check_recipient_warnings(recipient: &Pubkey, warnings: Vec<RecipientWarning>) -> Result<(), Error> {
+    match warnings.into_iter().next() {
+        Some(warning) if warning.severity() == Severity::Blocking => Err(Error::RiskyRecipient(*recipient, warning)),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,122 @@
+//! Pre-flight simulation of prepared transactions.
+//!
+//! Running a prepared `Message` through `simulateTransaction` before the user signs catches
+//! failures, e.g. funding a new account below the rent-exempt minimum, while no money is at stake.
+
+use crate::{Error, Lamports, PayClient};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::rpc_config::{RpcSimulateTransactionAccountsConfig, RpcSimulateTransactionConfig};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{
+    message::Message,
+    transaction::{Transaction, TransactionError},
+};
+
+/// The SOL balance of an account before and after the simulated transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BalanceChange {
+    pub address: Pubkey,
+    pub pre_balance: Lamports,
+    pub post_balance: Lamports,
+}
+
+impl BalanceChange {
+    /// Returns the change in lamports, negative when the account paid.
+    pub fn delta(&self) -> i128 {
+        self.post_balance.lamports() as i128 - self.pre_balance.lamports() as i128
+    }
+}
+
+/// The outcome of `PayClient::simulate_transaction`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Simulation {
+    /// Program logs, in execution order.
+    pub logs: Vec<String>,
+    /// Compute units used, e.g. to size `PriorityFee::Fixed::compute_unit_limit`. Not reported
+    /// by older nodes.
+    pub units_consumed: Option<u64>,
+    /// Balance changes of the writable accounts of the transaction.
+    pub balance_changes: Vec<BalanceChange>,
+    /// Why the transaction would fail, if it would.
+    pub error: Option<TransactionError>,
+}
+
+impl PayClient {
+    /// Simulates `message`, e.g. a `PreparedTransaction::message`, without signing it. The
+    /// blockhash is replaced by the latest one, so an expired message can still be simulated.
+    ///
+    /// Pre-balances are read just before the simulation, so transactions landing in between can
+    /// show up in `Simulation::balance_changes`.
+    pub fn simulate_transaction(&self, message: &Message) -> Result<Simulation, Error> {
+        let rpc_client = self.rpc_client();
+        let writable_accounts = writable_accounts(message);
+
+        let pre_accounts = rpc_client
+            .get_multiple_accounts_with_commitment(&writable_accounts, self.commitment())?
+            .value;
+        let result = rpc_client
+            .simulate_transaction_with_config(
+                &Transaction::new_unsigned(message.clone()),
+                RpcSimulateTransactionConfig {
+                    sig_verify: false,
+                    replace_recent_blockhash: true,
+                    commitment: Some(self.commitment()),
+                    accounts: Some(RpcSimulateTransactionAccountsConfig {
+                        encoding: Some(UiAccountEncoding::Base64),
+                        addresses: writable_accounts.iter().map(|address| address.to_string()).collect(),
+                    }),
+                    ..RpcSimulateTransactionConfig::default()
+                },
+            )?
+            .value;
+
+        // Accounts are only returned when the simulation succeeded.
+        let post_balances: Vec<Option<u64>> = match result.accounts {
+            Some(accounts) => accounts
+                .iter()
+                .map(|account| Some(account.as_ref().map_or(0, |account| account.lamports)))
+                .collect(),
+            None => vec![None; writable_accounts.len()],
+        };
+        let balance_changes = writable_accounts
+            .iter()
+            .zip(pre_accounts)
+            .zip(post_balances)
+            .map(|((address, pre_account), post_balance)| {
+                let pre_balance = pre_account.map_or(0, |account| account.lamports);
+                BalanceChange {
+                    address: *address,
+                    pre_balance: Lamports(pre_balance),
+                    post_balance: Lamports(post_balance.unwrap_or(pre_balance)),
+                }
+            })
+            .collect();
+
+        Ok(Simulation {
+            logs: result.logs.unwrap_or_default(),
+            units_consumed: result.units_consumed,
+            balance_changes,
+            error: result.err,
+        })
+    }
+}
+
+/// Returns the accounts `message` locks for writing, according to its header.
+fn writable_accounts(message: &Message) -> Vec<Pubkey> {
+    let header = &message.header;
+    let num_signed = header.num_required_signatures as usize;
+    let num_writable_signed = num_signed.saturating_sub(header.num_readonly_signed_accounts as usize);
+    let num_writable_unsigned = message
+        .account_keys
+        .len()
+        .saturating_sub(num_signed)
+        .saturating_sub(header.num_readonly_unsigned_accounts as usize);
+
+    message
+        .account_keys
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i < num_writable_signed || (*i >= num_signed && *i < num_signed + num_writable_unsigned))
+        .map(|(_, address)| *address)
+        .collect()
+}
@@ -0,0 +1,388 @@
+//! SPL Token and Token-2022 balances and transfers.
+//!
+//! Instructions are encoded by hand rather than through the `spl-token` crates, which would pull in
+//! a second copy of `solana-program` next to the pinned git revision.
+
+use crate::{
+    build_message_with_blockhash, compute_budget, check_memo_fits, check_remaining_balance,
+    check_transaction_size, resolve_blockhash, Error, Lamports, PayClient, PreparedTransaction,
+    TokenAmount, TransactionConfig, WithMemo,
+};
+use solana_account_decoder::{
+    parse_token::{TokenAccountType, UiAccountState},
+    UiAccountData,
+};
+use solana_client::{rpc_request::TokenAccountsFilter, rpc_response::RpcKeyedAccount};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    system_program,
+};
+use std::convert::TryInto;
+use std::str::FromStr;
+
+pub mod token_program {
+    solana_sdk::declare_id!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+}
+
+pub mod token_2022_program {
+    solana_sdk::declare_id!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+}
+
+pub mod associated_token_program {
+    solana_sdk::declare_id!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+}
+
+const MINT_LEN: usize = 82;
+const MINT_DECIMALS_OFFSET: usize = 44;
+const MINT_IS_INITIALIZED_OFFSET: usize = 45;
+const ACCOUNT_AMOUNT_OFFSET: usize = 64;
+const ACCOUNT_LEN: usize = 165;
+
+/// Offset of the `AccountType` byte Token-2022 appends to mints and accounts with extensions.
+const ACCOUNT_TYPE_OFFSET: usize = ACCOUNT_LEN;
+const ACCOUNT_TYPE_MINT: u8 = 1;
+const ACCOUNT_TYPE_ACCOUNT: u8 = 2;
+/// Each Token-2022 extension is stored after the account type as a `u16` type, a `u16` length and
+/// its data.
+const EXTENSIONS_OFFSET: usize = ACCOUNT_TYPE_OFFSET + 1;
+const EXTENSION_HEADER_LEN: usize = 4;
+/// Token-2022 associated token accounts always carry the `ImmutableOwner` extension, which has no
+/// data.
+const ACCOUNT_2022_LEN: usize = EXTENSIONS_OFFSET + EXTENSION_HEADER_LEN;
+/// Extension type of `TransferHook`, whose data is an authority and the hook program id.
+const TRANSFER_HOOK: u16 = 14;
+const TRANSFER_HOOK_PROGRAM_ID_OFFSET: usize = 32;
+
+/// Instruction tag of `TokenInstruction::Transfer`.
+pub(crate) const TRANSFER: u8 = 3;
+/// Instruction tag of `TokenInstruction::TransferChecked`.
+pub(crate) const TRANSFER_CHECKED: u8 = 12;
+/// Instruction tag of `AssociatedTokenAccountInstruction::CreateIdempotent`.
+const CREATE_IDEMPOTENT: u8 = 1;
+
+/// A token account owned by a wallet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenBalance {
+    /// Address of the token account.
+    pub address: Pubkey,
+    pub mint: Pubkey,
+    /// The SPL Token or Token-2022 program owning the account.
+    pub program_id: Pubkey,
+    pub amount: TokenAmount,
+    /// Frozen accounts can't send or receive tokens until thawed by the mint's freeze authority.
+    pub is_frozen: bool,
+}
+
+impl TokenBalance {
+    /// Returns the balance in whole tokens for display, e.g. `"1.5"` for 1_500_000 base units of
+    /// a 6 decimals mint.
+    pub fn ui_amount(&self) -> String {
+        self.amount.to_string()
+    }
+}
+
+/// Returns true for the SPL Token and Token-2022 program ids.
+pub fn is_token_program(program_id: &Pubkey) -> bool {
+    token_program::check_id(program_id) || token_2022_program::check_id(program_id)
+}
+
+/// Returns the address of `owner`'s associated token account for `mint`. `token_program_id` is the
+/// owner of the mint account.
+pub fn get_associated_token_address(owner: &Pubkey, mint: &Pubkey, token_program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[owner.as_ref(), token_program_id.as_ref(), mint.as_ref()],
+        &associated_token_program::id(),
+    )
+    .0
+}
+
+/// Returns the data length of a new associated token account for the mint whose account data is
+/// `mint_data`, which is needed to compute its rent. Token-2022 mints with some extensions, e.g. a
+/// transfer fee, require larger accounts.
+///
+/// Returns `None` if the mint has a Token-2022 extension unknown to this crate, whose effect on
+/// the account size can't be known.
+pub fn associated_token_account_len(mint_data: &[u8], token_program_id: &Pubkey) -> Option<usize> {
+    if !token_2022_program::check_id(token_program_id) {
+        return Some(ACCOUNT_LEN);
+    }
+
+    let mut len = ACCOUNT_2022_LEN;
+    for (extension_type, _) in mint_extensions(mint_data) {
+        // Data length of the account extension each mint extension requires, as in
+        // `ExtensionType::get_required_init_account_extensions` of `spl-token-2022`.
+        let account_extension_len = match extension_type {
+            // `TransferFeeConfig` requires `TransferFeeAmount`.
+            1 => Some(8),
+            // `NonTransferable` requires `NonTransferableAccount`.
+            9 => Some(0),
+            // `TransferHook` requires `TransferHookAccount`.
+            TRANSFER_HOOK => Some(1),
+            // `Pausable` requires `PausableAccount`.
+            26 => Some(0),
+            // Mint extensions requiring nothing from accounts.
+            3 | 4 | 6 | 10 | 12 | 16 | 18..=25 => None,
+            _ => return None,
+        };
+        if let Some(account_extension_len) = account_extension_len {
+            len += EXTENSION_HEADER_LEN + account_extension_len;
+        }
+    }
+
+    Some(len)
+}
+
+/// Returns the program the `TransferHook` extension of a Token-2022 mint invokes on every
+/// transfer, if it has one. Such transfers need extra accounts defined by the hook program, which
+/// this crate doesn't resolve.
+pub fn transfer_hook_program(mint_data: &[u8], token_program_id: &Pubkey) -> Option<Pubkey> {
+    if !token_2022_program::check_id(token_program_id) {
+        return None;
+    }
+
+    let (_, data) = mint_extensions(mint_data).into_iter().find(|(extension_type, _)| *extension_type == TRANSFER_HOOK)?;
+    let program_id = data.get(TRANSFER_HOOK_PROGRAM_ID_OFFSET..TRANSFER_HOOK_PROGRAM_ID_OFFSET + 32)?;
+    // An unset program id is stored as zeros.
+    Some(Pubkey::new(program_id)).filter(|program_id| *program_id != Pubkey::default())
+}
+
+/// Returns the type and data of each extension of a Token-2022 mint, in order.
+fn mint_extensions(mint_data: &[u8]) -> Vec<(u16, &[u8])> {
+    let mut extensions = vec![];
+    let mut data = mint_data.get(EXTENSIONS_OFFSET..).unwrap_or_default();
+    while data.len() >= EXTENSION_HEADER_LEN {
+        let extension_type = u16::from_le_bytes([data[0], data[1]]);
+        let extension_len = u16::from_le_bytes([data[2], data[3]]) as usize;
+        // Padding after the last extension.
+        if extension_type == 0 {
+            break;
+        }
+        let rest = &data[EXTENSION_HEADER_LEN..];
+        extensions.push((extension_type, rest.get(..extension_len).unwrap_or(rest)));
+        data = rest.get(extension_len..).unwrap_or_default();
+    }
+
+    extensions
+}
+
+/// What an account owned by a token program holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TokenAccountKind {
+    Mint,
+    Account { mint: Pubkey, owner: Pubkey },
+}
+
+/// Tells mints and token accounts apart from their data length, or from the account type byte of
+/// Token-2022 accounts with extensions.
+pub(crate) fn token_account_kind(data: &[u8]) -> Option<TokenAccountKind> {
+    let account_type = match data.len() {
+        MINT_LEN => ACCOUNT_TYPE_MINT,
+        ACCOUNT_LEN => ACCOUNT_TYPE_ACCOUNT,
+        len if len > ACCOUNT_LEN => data[ACCOUNT_TYPE_OFFSET],
+        _ => return None,
+    };
+
+    match account_type {
+        ACCOUNT_TYPE_MINT => Some(TokenAccountKind::Mint),
+        ACCOUNT_TYPE_ACCOUNT => Some(TokenAccountKind::Account {
+            mint: Pubkey::new(&data[..32]),
+            owner: Pubkey::new(&data[32..64]),
+        }),
+        _ => None,
+    }
+}
+
+impl PayClient {
+    /// Returns every SPL Token and Token-2022 account owned by the given wallet address,
+    /// including empty ones. Accounts the RPC node returns in an unexpected shape are skipped
+    /// rather than failing the whole call.
+    pub fn get_token_balances(&self, base58_pubkey: &str) -> Result<Vec<TokenBalance>, Error> {
+        let owner = Pubkey::from_str(base58_pubkey)?;
+
+        let mut balances = vec![];
+        for program_id in [token_program::id(), token_2022_program::id()] {
+            let accounts = self
+                .rpc_client()
+                .get_token_accounts_by_owner_with_commitment(&owner, TokenAccountsFilter::ProgramId(program_id), self.commitment())?
+                .value;
+            balances.extend(accounts.iter().filter_map(|keyed_account| parse_token_balance(keyed_account, program_id).ok()));
+        }
+
+        Ok(balances)
+    }
+
+    /// Prepares a transaction to send `amount` tokens of `mint` from `sender` to `recipient`'s
+    /// wallet address. The decimals of `amount` must match the mint's. The transaction can later
+    /// be finished by `finish_transaction`.
+    ///
+    /// Tokens move between the associated token accounts of both wallets. If the recipient's
+    /// doesn't exist yet, it is created by the same transaction at the fee payer's expense and its
+    /// rent is included in `PreparedTransaction::fee`.
+    ///
+    /// Token-2022 mints with a transfer hook are refused with `Error::BadParameter`. With a
+    /// transfer fee, the recipient receives `amount` minus the mint's fee.
+    pub fn create_token_transaction(&self, sender: &Pubkey, mint: &Pubkey, amount: TokenAmount, recipient: &Pubkey, config: &TransactionConfig) -> Result<PreparedTransaction, Error> {
+        let rpc_client = self.rpc_client();
+        let commitment = self.commitment();
+        let fee_payer = config.fee_payer.as_ref().unwrap_or(sender);
+        let nonce_authority = config.nonce_authority.as_ref().unwrap_or(sender);
+
+        // Best case, without creating the recipient's account; the exact check follows below.
+        let source = get_associated_token_address(sender, mint, &token_program::id());
+        let destination = get_associated_token_address(recipient, mint, &token_program::id());
+        let transfer = transfer_checked(&token_program::id(), &source, mint, &destination, sender, amount.amount, amount.decimals);
+        check_memo_fits(vec![transfer], fee_payer, nonce_authority, config)?;
+        if config.refuse_risky_recipients {
+            self.check_recipient(sender, recipient)?;
+        }
+
+        let mint_account = rpc_client
+            .get_account_with_commitment(mint, commitment)?
+            .value
+            .ok_or(Error::InvalidMint(*mint))?;
+        let token_program_id = mint_account.owner;
+        if !is_token_program(&token_program_id)
+            || mint_account.data.len() < MINT_LEN
+            || mint_account.data[MINT_IS_INITIALIZED_OFFSET] != 1
+        {
+            return Err(Error::InvalidMint(*mint));
+        }
+        let decimals = mint_account.data[MINT_DECIMALS_OFFSET];
+        if amount.decimals != decimals {
+            return Err(Error::BadParameter(format!(
+                "amount has {} decimals, mint {} has {}",
+                amount.decimals, mint, decimals
+            )));
+        }
+        if let Some(hook_program_id) = transfer_hook_program(&mint_account.data, &token_program_id) {
+            return Err(Error::BadParameter(format!(
+                "mint {} has a transfer hook ({}), which is not supported",
+                mint, hook_program_id
+            )));
+        }
+        let amount = amount.amount;
+
+        let source = get_associated_token_address(sender, mint, &token_program_id);
+        let destination = get_associated_token_address(recipient, mint, &token_program_id);
+        let accounts = rpc_client
+            .get_multiple_accounts_with_commitment(&[source, destination], commitment)?
+            .value;
+
+        let source_balance = accounts[0]
+            .as_ref()
+            .and_then(|account| account.data.get(ACCOUNT_AMOUNT_OFFSET..ACCOUNT_AMOUNT_OFFSET + 8))
+            .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+            .unwrap_or_default();
+        if source_balance < amount {
+            return Err(Error::InsufficientTokenFunds(TokenAmount::new(amount, decimals), source));
+        }
+
+        let mut ixs = config.priority_fee.instructions(rpc_client, &[*fee_payer, source, destination])?;
+        let rent = if accounts[1].is_none() {
+            ixs.push(create_associated_token_account_idempotent(fee_payer, recipient, mint, &token_program_id));
+            let account_len = associated_token_account_len(&mint_account.data, &token_program_id).ok_or_else(|| {
+                Error::BadParameter(format!("mint {} has an unsupported Token-2022 extension", mint))
+            })?;
+            rpc_client.get_minimum_balance_for_rent_exemption(account_len)?
+        } else {
+            0
+        };
+        ixs.push(transfer_checked(&token_program_id, &source, mint, &destination, sender, amount, decimals));
+        let ixs = ixs.with_memo(config.memo.as_ref());
+
+        let (recent_blockhash, last_valid_block_height) = resolve_blockhash(rpc_client, commitment, config.blockhash, config.nonce_account.as_ref())?;
+        let message = build_message_with_blockhash(ixs, fee_payer, config.nonce_account.as_ref(), nonce_authority, &recent_blockhash);
+        check_transaction_size(&message, config.memo.as_ref())?;
+
+        let fee = compute_budget::get_fee_for_message(rpc_client, &message)?.saturating_add(rent);
+        let fee_balance = rpc_client.get_balance_with_commitment(fee_payer, commitment)?.value;
+        if fee_balance == 0 || fee_balance < fee {
+            return Err(Error::InsufficientFundsForFee(Lamports(fee), *fee_payer));
+        }
+        let rent_exempt_minimum = rpc_client.get_minimum_balance_for_rent_exemption(0)?;
+        check_remaining_balance(fee_payer, Lamports(fee_balance), Lamports(fee), Lamports(rent_exempt_minimum))?;
+
+        Ok(PreparedTransaction {
+            message,
+            fee: Lamports(fee),
+            amount: TokenAmount::new(amount, decimals),
+            last_valid_block_height,
+        })
+    }
+}
+
+/// Decodes a token account returned with `jsonParsed` encoding, e.g. by
+/// `getTokenAccountsByOwner`. `program_id` is the token program owning it.
+pub fn parse_token_balance(keyed_account: &RpcKeyedAccount, program_id: Pubkey) -> Result<TokenBalance, Error> {
+    let unexpected = || Error::RpcRequestError(format!("Unexpected data for token account {}", keyed_account.pubkey));
+
+    let parsed = match &keyed_account.account.data {
+        UiAccountData::Json(parsed_account) => parsed_account.parsed.clone(),
+        _ => return Err(unexpected()),
+    };
+    let token_account = match serde_json::from_value(parsed) {
+        Ok(TokenAccountType::Account(token_account)) => token_account,
+        _ => return Err(unexpected()),
+    };
+
+    Ok(TokenBalance {
+        address: Pubkey::from_str(&keyed_account.pubkey)?,
+        mint: Pubkey::from_str(&token_account.mint)?,
+        program_id,
+        amount: TokenAmount::new(
+            token_account.token_amount.amount.parse().map_err(|_| unexpected())?,
+            token_account.token_amount.decimals,
+        ),
+        is_frozen: token_account.state == UiAccountState::Frozen,
+    })
+}
+
+/// Builds a `TransferChecked` instruction of the SPL Token or Token-2022 program, which fails unless
+/// `decimals` match the mint's.
+pub fn transfer_checked(
+    token_program_id: &Pubkey,
+    source: &Pubkey,
+    mint: &Pubkey,
+    destination: &Pubkey,
+    authority: &Pubkey,
+    amount: u64,
+    decimals: u8,
+) -> Instruction {
+    let mut data = vec![TRANSFER_CHECKED];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.push(decimals);
+
+    Instruction {
+        program_id: *token_program_id,
+        accounts: vec![
+            AccountMeta::new(*source, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data,
+    }
+}
+
+/// Builds an instruction creating `owner`'s associated token account for `mint` at `funder`'s
+/// expense, which succeeds if the account already exists.
+pub fn create_associated_token_account_idempotent(
+    funder: &Pubkey,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: associated_token_program::id(),
+        accounts: vec![
+            AccountMeta::new(*funder, true),
+            AccountMeta::new(get_associated_token_address(owner, mint, token_program_id), false),
+            AccountMeta::new_readonly(*owner, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(*token_program_id, false),
+        ],
+        data: vec![CREATE_IDEMPOTENT],
+    }
+}
@@ -0,0 +1,112 @@
+use stream_pay_core as core;
+
+use core::{Lamports, TokenAmount};
+
+/// Parses and formats amounts exactly, without going through `f64`.
+#[test]
+fn parse_and_format() {
+    assert_eq!("1.5".parse::<Lamports>().unwrap(), Lamports(1_500_000_000));
+    assert_eq!("0.000000001".parse::<Lamports>().unwrap(), Lamports(1));
+    assert_eq!(".25".parse::<Lamports>().unwrap(), Lamports(250_000_000));
+    assert_eq!("2.".parse::<Lamports>().unwrap(), Lamports(2_000_000_000));
+    assert_eq!("0.1000000000".parse::<Lamports>().unwrap(), Lamports(100_000_000));
+    assert_eq!("18446744073.709551615".parse::<Lamports>().unwrap(), Lamports(u64::MAX));
+
+    assert!("0.0000000001".parse::<Lamports>().is_err());
+    assert!("18446744073.709551616".parse::<Lamports>().is_err());
+    assert!("-1".parse::<Lamports>().is_err());
+    assert!("1e9".parse::<Lamports>().is_err());
+    assert!(".".parse::<Lamports>().is_err());
+    assert!("".parse::<Lamports>().is_err());
+
+    assert_eq!(Lamports(1_500_000_000).to_string(), "1.5");
+    assert_eq!(Lamports(1).to_string(), "0.000000001");
+    assert_eq!(Lamports::ZERO.to_string(), "0");
+
+    let usdc = TokenAmount::parse("10.25", 6).unwrap();
+    assert_eq!(usdc, TokenAmount::new(10_250_000, 6));
+    assert_eq!(usdc.to_string(), "10.25");
+    assert!(TokenAmount::parse("0.1", 0).is_err());
+    assert_eq!(TokenAmount::new(42, 0).to_string(), "42");
+}
+
+/// Arithmetic is checked, and token amounts with different decimals don't mix.
+#[test]
+fn arithmetic() {
+    assert_eq!(Lamports(5).checked_sub(Lamports(3)), Some(Lamports(2)));
+    assert_eq!(Lamports(3).checked_sub(Lamports(5)), None);
+    assert_eq!(Lamports(u64::MAX).checked_add(Lamports(1)), None);
+    assert_eq!(Lamports(3).saturating_sub(Lamports(5)), Lamports::ZERO);
+
+    let a = TokenAmount::new(1, 6);
+    assert_eq!(a.checked_add(a), Some(TokenAmount::new(2, 6)));
+    assert_eq!(a.checked_add(TokenAmount::new(1, 9)), None);
+    assert_eq!(TokenAmount::from(Lamports(7)), TokenAmount::new(7, 9));
+}
+
+/// Parses user input with units and locale separators.
+#[test]
+fn parse_user_input() {
+    use core::amount::{mainnet_mints, parse_amount, Amount, NumberFormat, ParseConfig};
+
+    let usdc = mainnet_mints()[0].mint;
+    let config = ParseConfig::new(NumberFormat::default(), mainnet_mints(), None).unwrap();
+    assert_eq!(parse_amount("1.5", &config).unwrap(), Amount::Sol(Lamports(1_500_000_000)));
+    assert_eq!(parse_amount(" 0.25 SOL ", &config).unwrap(), Amount::Sol(Lamports(250_000_000)));
+    assert_eq!(parse_amount("0.25sol", &config).unwrap(), Amount::Sol(Lamports(250_000_000)));
+    assert_eq!(parse_amount("2500000 lamports", &config).unwrap(), Amount::Sol(Lamports(2_500_000)));
+    assert_eq!(
+        parse_amount("10 USDC", &config).unwrap(),
+        Amount::Token { mint: usdc, amount: TokenAmount::new(10_000_000, 6) }
+    );
+    assert!(parse_amount("0.5 lamports", &config).is_err());
+    assert!(parse_amount("0.0000001 USDC", &config).is_err());
+    assert!(parse_amount("10 BONK", &config).is_err());
+    assert!(parse_amount("1,5", &config).is_err());
+
+    let european = NumberFormat {
+        decimal_separator: ',',
+        group_separator: Some('.'),
+        ..NumberFormat::default()
+    };
+    let config = ParseConfig::new(european, mainnet_mints(), Some(usdc)).unwrap();
+    assert_eq!(
+        parse_amount("1,5", &config).unwrap(),
+        Amount::Token { mint: usdc, amount: TokenAmount::new(1_500_000, 6) }
+    );
+    assert_eq!(parse_amount("1.000,5 SOL", &config).unwrap(), Amount::Sol(Lamports(1_000_500_000_000)));
+    assert!(parse_amount("1.00,5 SOL", &config).is_err());
+    assert!(parse_amount("1.5 SOL", &config).is_err());
+
+    let ambiguous = NumberFormat {
+        group_separator: Some('.'),
+        ..NumberFormat::default()
+    };
+    assert!(matches!(ParseConfig::new(ambiguous, mainnet_mints(), None), Err(core::Error::BadParameter(_))));
+    let unknown_default = core::Pubkey::new_unique();
+    assert!(matches!(ParseConfig::new(european, mainnet_mints(), Some(unknown_default)), Err(core::Error::BadParameter(_))));
+    assert!(matches!(ParseConfig::new(european, vec![], Some(usdc)), Err(core::Error::BadParameter(_))));
+}
+
+/// Formats with grouping, another decimal separator and without trimming.
+#[test]
+fn format() {
+    use core::amount::NumberFormat;
+
+    assert_eq!(Lamports(1_234_567_500_000_000).format(&NumberFormat::default()), "1234567.5");
+
+    let grouped = NumberFormat {
+        group_separator: Some(','),
+        ..NumberFormat::default()
+    };
+    assert_eq!(Lamports(1_234_567_500_000_000).format(&grouped), "1,234,567.5");
+    assert_eq!(Lamports(123_000_000_000).format(&grouped), "123");
+
+    let european = NumberFormat {
+        decimal_separator: ',',
+        group_separator: Some('.'),
+        trim_trailing_zeros: false,
+    };
+    assert_eq!(TokenAmount::new(1_234_500_000, 6).format(&european), "1.234,500000");
+    assert_eq!(TokenAmount::new(1_000, 0).format(&european), "1.000");
+}
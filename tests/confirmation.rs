@@ -0,0 +1,30 @@
+use stream_pay_core as core;
+
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::Signer;
+use solana_sdk::signer::keypair::Keypair;
+
+use std::time::Duration;
+
+mod test_helpers;
+use test_helpers::{RPC_ENDPOINT, TESTNET_KEY};
+
+/// Sends 0.001 SOL back to the sending address and waits until it is confirmed.
+#[test]
+fn main() {
+    let sender = Keypair::from_bytes(&*TESTNET_KEY).unwrap();
+    let sender_pubkey = sender.pubkey();
+    let client = core::PayClient::new(core::Cluster::Custom(RPC_ENDPOINT.to_string()));
+
+    let prepared = client.create_transaction(&sender_pubkey, core::Lamports(1_000_000), &sender_pubkey).expect("Failed to prepare transaction");
+    let signature = client.finish_transaction(&sender, prepared.message).expect("Failed to finish transaction");
+
+    let signature = signature.parse().expect("Signature is not base58");
+    let mut polls = 0;
+    let confirmation = client
+        .wait_for_confirmation(&signature, CommitmentConfig::confirmed(), Duration::from_secs(60), |_progress| polls += 1)
+        .expect("Transaction was not confirmed");
+    println!("Confirmed in slot {} after {} polls", confirmation.slot, polls);
+    assert_eq!(confirmation.signature, signature);
+    assert!(confirmation.status.is_ok());
+}
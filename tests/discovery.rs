@@ -0,0 +1,91 @@
+use stream_pay_core as core;
+
+use core::discovery::{scan_accounts, DiscoveredAccount, DiscoveryConfig};
+use core::keys::{derive_keypair, DerivationPreset};
+use core::{Error, Lamports};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signer;
+use std::sync::Mutex;
+
+mod test_helpers;
+use test_helpers::RPC_ENDPOINT;
+
+/// Scans a fresh mnemonic, which has no used accounts, and the public BIP39 test mnemonic, whose
+/// accounts may have been used by anyone.
+#[test]
+fn main() {
+    let client = core::PayClient::new(core::Cluster::Custom(RPC_ENDPOINT.to_string()));
+    let config = DiscoveryConfig {
+        gap_limit: 2,
+        ..DiscoveryConfig::default()
+    };
+
+    let fresh = "legal winner thank year wave sausage worth useful legal winner thank yellow";
+    let discovery = client.discover_accounts(fresh, "stream-pay-core discovery test", &config).unwrap();
+    assert!(discovery.accounts.is_empty());
+    assert!(discovery.failed.is_empty());
+
+    let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+    let accounts = client.discover_accounts(mnemonic, "", &config).unwrap().accounts;
+    for account in &accounts {
+        assert!(account.is_used());
+        assert_eq!(account.path, account.preset.path(account.index));
+        assert_eq!(derive_keypair(mnemonic, "", &account.path).unwrap().pubkey(), account.address);
+        if account.preset == DerivationPreset::Bip44Root {
+            assert_eq!(account.index, 0);
+        }
+    }
+    assert!(accounts.windows(2).all(|pair| {
+        let position = |preset| DerivationPreset::ALL.iter().position(|p| *p == preset);
+        (position(pair[0].preset), pair[0].index) < (position(pair[1].preset), pair[1].index)
+    }));
+}
+
+/// Stops each preset after `gap_limit` consecutive unused or failed indices, however many checks
+/// run at once, and keeps the accounts found around a failure.
+#[test]
+fn scan() {
+    use DerivationPreset::*;
+    let used = [(Bip44Change, 0), (Bip44Change, 3), (Bip44Change, 7), (Bip44, 1), (Bip44Root, 0)];
+    let config = DiscoveryConfig {
+        gap_limit: 3,
+        ..DiscoveryConfig::default()
+    };
+
+    for max_concurrent_checks in [1, 2, 8] {
+        let checked = Mutex::new(vec![]);
+        let discovery = scan_accounts(&config, max_concurrent_checks, |preset, index| {
+            checked.lock().unwrap().push((preset, index));
+            if (preset, index) == (Bip44, 0) {
+                return Err(Error::RpcRequestError("timed out".to_string()));
+            }
+            Ok(DiscoveredAccount {
+                preset,
+                index,
+                path: preset.path(index),
+                address: Pubkey::new_unique(),
+                balance: Lamports(used.contains(&(preset, index)) as u64),
+                token_balances: vec![],
+                has_history: false,
+            })
+        })
+        .unwrap();
+
+        let found = discovery.accounts.iter().map(|account| (account.preset, account.index)).collect::<Vec<_>>();
+        assert_eq!(found, [(Bip44Change, 0), (Bip44Change, 3), (Bip44, 1), (Bip44Root, 0)]);
+        assert_eq!(discovery.failed.len(), 1);
+        assert_eq!((discovery.failed[0].preset, discovery.failed[0].index), (Bip44, 0));
+
+        let mut checked = checked.into_inner().unwrap();
+        checked.sort_by_key(|(preset, index)| (DerivationPreset::ALL.iter().position(|p| p == preset), *index));
+        let expected = (0..7).map(|index| (Bip44Change, index)).chain((0..5).map(|index| (Bip44, index))).chain([(Bip44Root, 0)]);
+        assert_eq!(checked, expected.collect::<Vec<_>>(), "{} concurrent checks", max_concurrent_checks);
+    }
+
+    let config = DiscoveryConfig {
+        gap_limit: 0,
+        ..DiscoveryConfig::default()
+    };
+    let result = scan_accounts(&config, 1, |_, _| -> Result<DiscoveredAccount, Error> { panic!("checked with a gap limit of 0") });
+    assert!(matches!(result, Err(Error::BadParameter(_))));
+}
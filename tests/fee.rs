@@ -12,11 +12,13 @@ use test_helpers::{RPC_ENDPOINT, TESTNET_KEY};
 fn main() {
     let sender = Keypair::from_bytes(&*TESTNET_KEY).unwrap();
     let sender_pubkey = sender.pubkey();
-    let initial_balance = core::get_balance(RPC_ENDPOINT, &sender_pubkey.to_string()).unwrap();
-    println!("Initial balance: {:.6}", initial_balance);
+    let client = core::PayClient::new(core::Cluster::Custom(RPC_ENDPOINT.to_string()));
+    let initial_balance = client.get_balance(&sender_pubkey.to_string()).unwrap();
+    println!("Initial balance: {}", initial_balance);
 
-    let core::PreparedTransaction { message, fee } = core::create_transaction(RPC_ENDPOINT, &sender_pubkey, 0.5, &sender_pubkey).expect("Failed to prepare transaction");
-    println!("Estimated fee: {:.6}", fee);
+    let amount = "0.5".parse().unwrap();
+    let core::PreparedTransaction { message, fee, .. } = client.create_transaction(&sender_pubkey, amount, &sender_pubkey).expect("Failed to prepare transaction");
+    println!("Estimated fee: {}", fee);
 
     let _signature = core::finish_transaction(RPC_ENDPOINT, &sender, message).expect("Failed to finish transaction");
 
@@ -24,7 +26,7 @@ fn main() {
     let final_balance = loop {
         std::thread::sleep(std::time::Duration::from_secs(5));
 
-        let new_balance = core::get_balance(RPC_ENDPOINT, &sender.pubkey().to_string()).unwrap();
+        let new_balance = client.get_balance(&sender.pubkey().to_string()).unwrap();
         if new_balance != initial_balance {
             break new_balance;
         }
@@ -36,6 +38,6 @@ fn main() {
         }
     };
 
-    println!("Final balance: {:.6}", final_balance);
-    assert_eq!(final_balance, initial_balance - fee);
+    println!("Final balance: {}", final_balance);
+    assert_eq!(Some(final_balance), initial_balance.checked_sub(fee));
 }
@@ -0,0 +1,190 @@
+use stream_pay_core as core;
+
+use core::history::{parse_payment, Asset, Direction, HistoryCursor};
+use core::token::{get_associated_token_address, token_program, transfer_checked};
+use solana_sdk::hash::Hash;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Signature, Signer};
+use solana_sdk::signer::keypair::Keypair;
+use solana_sdk::system_instruction;
+use solana_sdk::transaction::Transaction;
+use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+use std::str::FromStr;
+
+const USDC_MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+/// Returns `transaction` the way `getTransaction` does with base64 encoding.
+fn entry(transaction: &Transaction, meta: serde_json::Value) -> EncodedConfirmedTransactionWithStatusMeta {
+    let encoded = base64::encode(bincode::serialize(transaction).unwrap());
+    serde_json::from_value(serde_json::json!({
+        "slot": 42,
+        "blockTime": 1_650_000_000,
+        "transaction": [encoded, "base64"],
+        "meta": meta,
+    }))
+    .unwrap()
+}
+
+fn meta(err: serde_json::Value, pre_balances: &[u64], post_balances: &[u64]) -> serde_json::Value {
+    let status = if err.is_null() {
+        serde_json::json!({ "Ok": null })
+    } else {
+        serde_json::json!({ "Err": err })
+    };
+
+    serde_json::json!({
+        "err": err,
+        "status": status,
+        "fee": 5_000,
+        "preBalances": pre_balances,
+        "postBalances": post_balances,
+        "innerInstructions": [],
+        "logMessages": [],
+        "preTokenBalances": [],
+        "postTokenBalances": [],
+        "rewards": [],
+    })
+}
+
+/// Records a SOL payment from both sides, and a failed one from the transfer it attempted.
+#[test]
+fn sol_payment() {
+    let sender = Keypair::new();
+    let recipient = Keypair::new().pubkey();
+    let transaction = Transaction::new_signed_with_payer(
+        &[system_instruction::transfer(&sender.pubkey(), &recipient, 1_000_000)],
+        Some(&sender.pubkey()),
+        &[&sender],
+        Hash::new_unique(),
+    );
+
+    let succeeded = entry(&transaction, meta(serde_json::Value::Null, &[10_000_000, 0, 1], &[8_995_000, 1_000_000, 1]));
+    let record = parse_payment(&sender.pubkey(), &succeeded).unwrap();
+    assert_eq!(record.signature, transaction.signatures[0]);
+    assert_eq!(record.slot, 42);
+    assert_eq!(record.block_time, Some(1_650_000_000));
+    assert_eq!(record.direction, Direction::Outgoing);
+    assert_eq!(record.counterparty, Some(recipient));
+    assert_eq!(record.asset, Asset::Sol);
+    assert_eq!(record.amount, core::TokenAmount::new(1_000_000, 9));
+    assert_eq!(record.fee, core::Lamports(5_000));
+    assert_eq!(record.fee_payer, sender.pubkey());
+    assert!(record.status.is_ok());
+
+    let record = parse_payment(&recipient, &succeeded).unwrap();
+    assert_eq!(record.direction, Direction::Incoming);
+    assert_eq!(record.counterparty, Some(sender.pubkey()));
+    assert_eq!(record.amount.amount, 1_000_000);
+
+    // Only the fee left the sender's balance.
+    let err = serde_json::json!({ "InstructionError": [0, { "Custom": 1 }] });
+    let failed = entry(&transaction, meta(err, &[10_000_000, 0, 1], &[9_995_000, 0, 1]));
+    let record = parse_payment(&sender.pubkey(), &failed).unwrap();
+    assert_eq!(record.direction, Direction::Outgoing);
+    assert_eq!(record.counterparty, Some(recipient));
+    assert_eq!(record.amount.amount, 1_000_000);
+    assert_eq!(record.fee, core::Lamports(5_000));
+    assert!(record.status.is_err());
+
+    let record = parse_payment(&recipient, &failed).unwrap();
+    assert_eq!(record.direction, Direction::Incoming);
+    assert_eq!(record.counterparty, Some(sender.pubkey()));
+
+    assert!(parse_payment(&Pubkey::new_unique(), &succeeded).is_err());
+}
+
+/// Returns the balance of a token account the way `getTransaction` reports it.
+fn token_balance(account_index: usize, owner: &Pubkey, amount: u64) -> serde_json::Value {
+    serde_json::json!({
+        "accountIndex": account_index,
+        "mint": USDC_MINT,
+        "uiTokenAmount": { "uiAmount": null, "decimals": 6, "amount": amount.to_string(), "uiAmountString": "" },
+        "owner": owner.to_string(),
+    })
+}
+
+/// Records a token payment into an existing account from both sides, although the recipient's
+/// wallet isn't part of the transaction.
+#[test]
+fn token_payment() {
+    let sender = Keypair::new();
+    let recipient = Keypair::new().pubkey();
+    let mint = Pubkey::from_str(USDC_MINT).unwrap();
+    let source = get_associated_token_address(&sender.pubkey(), &mint, &token_program::id());
+    let destination = get_associated_token_address(&recipient, &mint, &token_program::id());
+    let transaction = Transaction::new_signed_with_payer(
+        &[transfer_checked(&token_program::id(), &source, &mint, &destination, &sender.pubkey(), 1_500_000, 6)],
+        Some(&sender.pubkey()),
+        &[&sender],
+        Hash::new_unique(),
+    );
+    let account_keys = &transaction.message.account_keys;
+    assert!(!account_keys.contains(&recipient));
+    let index = |key: &Pubkey| account_keys.iter().position(|k| k == key).unwrap();
+
+    let mut meta = meta(serde_json::Value::Null, &[10_000_000, 2_039_280, 2_039_280, 1, 1], &[9_995_000, 2_039_280, 2_039_280, 1, 1]);
+    meta["preTokenBalances"] = serde_json::json!([
+        token_balance(index(&source), &sender.pubkey(), 2_000_000),
+        token_balance(index(&destination), &recipient, 500_000),
+    ]);
+    meta["postTokenBalances"] = serde_json::json!([
+        token_balance(index(&source), &sender.pubkey(), 500_000),
+        token_balance(index(&destination), &recipient, 2_000_000),
+    ]);
+    let entry = entry(&transaction, meta);
+
+    let record = parse_payment(&recipient, &entry).unwrap();
+    assert_eq!(record.direction, Direction::Incoming);
+    assert_eq!(record.counterparty, Some(sender.pubkey()));
+    assert_eq!(record.asset, Asset::Token { mint, decimals: 6 });
+    assert_eq!(record.amount, core::TokenAmount::new(1_500_000, 6));
+    assert_eq!(record.fee_payer, sender.pubkey());
+
+    let record = parse_payment(&sender.pubkey(), &entry).unwrap();
+    assert_eq!(record.direction, Direction::Outgoing);
+    assert_eq!(record.counterparty, Some(recipient));
+    assert_eq!(record.amount.amount, 1_500_000);
+
+    assert!(parse_payment(&Pubkey::new_unique(), &entry).is_err());
+}
+
+/// Cursors survive being persisted as strings, and malformed ones are refused.
+#[test]
+fn cursor() {
+    let cursor: HistoryCursor = "".parse().unwrap();
+    assert_eq!(cursor, HistoryCursor::default());
+    assert_eq!(cursor.to_string(), "");
+
+    let signature = Signature::new(&[7; 64]);
+    let cursor: HistoryCursor = signature.to_string().parse().unwrap();
+    assert_eq!(cursor.to_string(), signature.to_string());
+    assert_eq!(cursor.to_string().parse::<HistoryCursor>().unwrap(), cursor);
+
+    for malformed in ["not a signature", "0OIl", &signature.to_string()[..40], " "] {
+        assert!(malformed.parse::<HistoryCursor>().is_err(), "{:?}", malformed);
+    }
+}
+
+/// Page sizes `getSignaturesForAddress` would refuse are rejected before any request.
+#[test]
+fn page_size() {
+    let client = core::PayClient::new(core::Cluster::Custom("http://127.0.0.1:1".to_string()));
+    let address = Keypair::new().pubkey();
+    let history = |page_size| client.transaction_history(&address, HistoryCursor::default(), None, page_size);
+
+    assert!(matches!(history(0), Err(core::Error::BadParameter(_))));
+    assert!(matches!(history(1_001), Err(core::Error::BadParameter(_))));
+    assert!(history(1).is_ok());
+    assert!(history(1_000).is_ok());
+}
+
+/// A page that fails to load is reported once and ends the iteration, so `flatten` can't loop.
+#[test]
+fn failed_page() {
+    let client = core::PayClient::new(core::Cluster::Custom("http://127.0.0.1:1".to_string()));
+    let mut history = client.transaction_history(&Keypair::new().pubkey(), HistoryCursor::default(), None, 10).unwrap();
+
+    assert!(matches!(history.next(), Some(Err(core::Error::ClientError(_)))));
+    assert!(history.next().is_none());
+    assert_eq!(history.cursor(), HistoryCursor::default());
+}
@@ -0,0 +1,106 @@
+use stream_pay_core as core;
+
+use core::keys::{import_key, KeyError, KeyFormat};
+use solana_sdk::signature::{keypair_from_seed_phrase_and_passphrase, Signer};
+use solana_sdk::signer::keypair::Keypair;
+
+const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+fn key_error(input: &str) -> KeyError {
+    match import_key(input, "") {
+        Err(core::Error::InvalidKey(err)) => err,
+        other => panic!("Expected a key error for {:?}, got {:?}", input, other),
+    }
+}
+
+/// Detects each supported format and imports the same keypair from all of them.
+#[test]
+fn formats() {
+    let keypair = Keypair::new();
+    let bytes = keypair.to_bytes();
+
+    let imported = import_key(&keypair.to_base58_string(), "").unwrap();
+    assert_eq!(imported.format, KeyFormat::Base58);
+    assert_eq!(imported.keypair.pubkey(), keypair.pubkey());
+
+    let imported = import_key(&format!(" {}\n", serde_json::to_string(&bytes.to_vec()).unwrap()), "").unwrap();
+    assert_eq!(imported.format, KeyFormat::JsonByteArray);
+    assert_eq!(imported.keypair.pubkey(), keypair.pubkey());
+
+    assert_eq!(core::keys::keypair_from_bytes(&bytes).unwrap().pubkey(), keypair.pubkey());
+    assert_eq!(core::keys::keypair_from_bytes(&bytes[..32]).unwrap().pubkey(), keypair.pubkey());
+
+    let expected = keypair_from_seed_phrase_and_passphrase(MNEMONIC, "secret").unwrap();
+    let imported = import_key(&MNEMONIC.to_uppercase().replace(' ', "  "), "secret").unwrap();
+    assert_eq!(imported.format, KeyFormat::Mnemonic);
+    assert_eq!(imported.keypair.pubkey(), expected.pubkey());
+
+    assert_eq!(core::private_key_to_pubkey(&keypair.to_base58_string()).unwrap(), keypair.pubkey().to_string());
+}
+
+/// Imports 32-byte seeds explicitly, as base58 or hex.
+#[test]
+fn seeds() {
+    use core::keys::import_seed;
+    use solana_sdk::bs58;
+
+    let keypair = Keypair::new();
+    let bytes = keypair.to_bytes();
+    let seed = &bytes[..32];
+    let hex: String = seed.iter().map(|byte| format!("{:02x}", byte)).collect();
+
+    assert_eq!(import_seed(&bs58::encode(seed).into_string()).unwrap().pubkey(), keypair.pubkey());
+    assert_eq!(import_seed(&format!(" {}\n", hex)).unwrap().pubkey(), keypair.pubkey());
+    assert_eq!(import_seed(&hex.to_uppercase()).unwrap().pubkey(), keypair.pubkey());
+
+    // `import_key` still refuses them, as they look like addresses.
+    assert_eq!(key_error(&bs58::encode(seed).into_string()), KeyError::PublicKey);
+
+    let seed_error = |input: &str| match import_seed(input) {
+        Err(core::Error::InvalidKey(err)) => err,
+        other => panic!("Expected a key error for {:?}, got {:?}", input, other),
+    };
+    assert_eq!(seed_error(""), KeyError::Empty);
+    assert_eq!(seed_error(&keypair.to_base58_string()), KeyError::InvalidLength(64));
+    assert_eq!(seed_error(&"0".repeat(62)), KeyError::UnrecognizedFormat);
+    assert_eq!(seed_error("not-base58!"), KeyError::UnrecognizedFormat);
+}
+
+/// Malformed input is reported, not panicked on.
+#[test]
+fn errors() {
+    let keypair = Keypair::new();
+    let mut bytes = keypair.to_bytes();
+
+    assert_eq!(key_error("  "), KeyError::Empty);
+    assert_eq!(key_error("not-base58!"), KeyError::UnrecognizedFormat);
+    assert_eq!(key_error(&keypair.pubkey().to_string()), KeyError::PublicKey);
+    assert_eq!(key_error("[1, 2, 3]"), KeyError::InvalidLength(3));
+    assert!(matches!(key_error("[1, 2, 300]"), KeyError::InvalidJson(_)));
+    assert!(matches!(key_error(&MNEMONIC.replace("about", "abandon")), KeyError::InvalidMnemonic(_)));
+    assert!(matches!(key_error("abandon abandon"), KeyError::InvalidMnemonic(_)));
+
+    bytes[63] ^= 1;
+    assert_eq!(key_error(&serde_json::to_string(&bytes.to_vec()).unwrap()), KeyError::PublicKeyMismatch);
+
+    assert!(core::private_key_to_pubkey("typo").is_err());
+}
+
+/// Derives the addresses other wallets show for the BIP39 test mnemonic.
+#[test]
+fn derivation() {
+    use core::keys::{derive_keypair, parse_derivation_path, DerivationPreset};
+
+    let derive = |path| derive_keypair(MNEMONIC, "", &path).unwrap().pubkey().to_string();
+    assert_eq!(derive(DerivationPreset::Bip44Change.path(0)), "HAgk14JpMQLgt6rVgv7cBQFJWFto5Dqxi472uT3DKpqk");
+    assert_eq!(derive(DerivationPreset::Bip44Change.path(1)), "Hh8QwFUA6MtVu1qAoq12ucvFHNwCcVTV7hpWjeY1Hztb");
+    assert_eq!(derive(DerivationPreset::Bip44.path(0)), "GjJyeC1r2RgkuoCWMyPYkCWSGSGLcz266EaAkLA27AhL");
+    assert_eq!(derive(DerivationPreset::Bip44Root.path(7)), "D2PPQSYFe83nDzk96FqGumVU8JA7J8vj2Rhjc2oXzEi5");
+
+    assert_eq!(parse_derivation_path("m/44'/501'/1'/0'").unwrap(), DerivationPreset::Bip44Change.path(1));
+    assert_eq!(parse_derivation_path("m/44h/501h/0h").unwrap(), DerivationPreset::Bip44.path(0));
+    assert_eq!(parse_derivation_path("m/44/501").unwrap(), DerivationPreset::Bip44Root.path(0));
+    for invalid in ["44'/501'/0'", "m/44'/60'/0'", "m/44'/501'/0'/0'/0'", "m/44'/501'/x'", "m/44'/501'/2147483648'"] {
+        assert!(matches!(parse_derivation_path(invalid), Err(core::Error::InvalidKey(KeyError::InvalidDerivationPath(_)))), "{}", invalid);
+    }
+}
@@ -0,0 +1,62 @@
+use stream_pay_core as core;
+
+use core::{Error, Lamports, PriorityFee, SpendAmount, TokenAmount, TransactionConfig};
+use solana_sdk::signature::Signer;
+use solana_sdk::signer::keypair::Keypair;
+
+/// No RPC server listens there, so any request fails with `Error::ClientError`.
+const UNREACHABLE_ENDPOINT: &str = "http://127.0.0.1:1";
+
+fn with_memo(len: usize, config: &TransactionConfig) -> TransactionConfig {
+    TransactionConfig {
+        memo: Some("m".repeat(len)),
+        ..config.clone()
+    }
+}
+
+/// Returns the longest memo that fits, as reported for a memo that doesn't.
+fn max_memo_len(transfer: &dyn Fn(&TransactionConfig) -> Result<core::PreparedTransaction, Error>, config: &TransactionConfig) -> usize {
+    match transfer(&with_memo(2_000, config)) {
+        Err(Error::MemoTooLong(2_000, max_len)) => max_len,
+        other => panic!("Expected Error::MemoTooLong, got {:?}", other),
+    }
+}
+
+/// Memos that can't fit are refused before any request, and the reported limit is exact.
+#[test]
+fn main() {
+    let client = core::PayClient::new(core::Cluster::Custom(UNREACHABLE_ENDPOINT.to_string()));
+    let sender = Keypair::new().pubkey();
+    let recipient = Keypair::new().pubkey();
+    let mint = Keypair::new().pubkey();
+
+    let transfer = |config: &TransactionConfig| client.create_transaction_with_config(&sender, SpendAmount::Some(Lamports(1)), &recipient, config);
+    let token_transfer = |config: &TransactionConfig| client.create_token_transaction(&sender, &mint, TokenAmount::new(1, 6), &recipient, config);
+
+    for transfer in [&transfer as &dyn Fn(&TransactionConfig) -> _, &token_transfer] {
+        let config = TransactionConfig::default();
+        let max_len = max_memo_len(transfer, &config);
+        assert!(matches!(transfer(&with_memo(max_len + 1, &config)), Err(Error::MemoTooLong(..))));
+        assert!(matches!(transfer(&with_memo(max_len, &config)), Err(Error::ClientError(_))));
+        assert!(matches!(transfer(&config), Err(Error::ClientError(_))));
+
+        // Compute budget instructions and another signer leave less room for the memo.
+        let priority_fee = TransactionConfig {
+            priority_fee: PriorityFee::Fixed { compute_unit_limit: 1_000, micro_lamports: 1 },
+            ..config.clone()
+        };
+        assert!(max_memo_len(transfer, &priority_fee) < max_len);
+        let fee_payer = TransactionConfig {
+            fee_payer: Some(Keypair::new().pubkey()),
+            ..config.clone()
+        };
+        assert!(max_memo_len(transfer, &fee_payer) < max_len);
+    }
+
+    // Multi-byte characters count by their UTF-8 length.
+    let config = TransactionConfig {
+        memo: Some("€".repeat(1_000)),
+        ..TransactionConfig::default()
+    };
+    assert!(matches!(transfer(&config), Err(Error::MemoTooLong(3_000, _))));
+}
@@ -0,0 +1,38 @@
+#![cfg(feature = "async")]
+
+use stream_pay_core as core;
+
+use solana_sdk::signature::Signer;
+use solana_sdk::signer::keypair::Keypair;
+
+mod test_helpers;
+use test_helpers::{RPC_ENDPOINT, TESTNET_KEY};
+
+/// Reads the balance, prepares a transfer and fetches the history through the async client.
+#[tokio::test]
+async fn main() {
+    let sender = Keypair::from_bytes(&*TESTNET_KEY).unwrap();
+    let sender_pubkey = sender.pubkey();
+    let client = core::nonblocking::PayClient::new(core::Cluster::Custom(RPC_ENDPOINT.to_string()));
+
+    let balance = client.get_balance(&sender_pubkey.to_string()).await.unwrap();
+    assert!(balance > core::Lamports::ZERO);
+
+    let core::PreparedTransaction { message, fee, amount, last_valid_block_height } = client
+        .create_transaction(&sender_pubkey, core::Lamports(1_000_000), &sender_pubkey)
+        .await
+        .expect("Failed to prepare transaction");
+    assert!(fee > core::Lamports::ZERO);
+    assert_eq!(amount, core::TokenAmount::new(1_000_000, 9));
+    assert_eq!(message.account_keys[0], sender_pubkey);
+    assert!(last_valid_block_height.is_some());
+
+    let limit = 5;
+    let history = client.get_transaction_history(&sender_pubkey, None, None, limit).await.unwrap();
+    assert!(history.len() <= limit);
+    for entry in &history {
+        let transaction = entry.transaction.as_ref().expect("Failed to fetch transaction");
+        let transaction = transaction.transaction.transaction.decode().unwrap();
+        assert_eq!(transaction.signatures[0], entry.signature);
+    }
+}
@@ -0,0 +1,88 @@
+use stream_pay_core as core;
+
+use core::nonce::{check_nonce_account, nonce_blockhash, transaction_signers};
+use core::Error;
+use solana_client::nonce_utils;
+use solana_sdk::account::Account;
+use solana_sdk::hash::Hash;
+use solana_sdk::message::Message;
+use solana_sdk::nonce::state::{Data, State, Versions};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signer;
+use solana_sdk::signer::keypair::Keypair;
+use solana_sdk::system_instruction;
+use solana_sdk::system_program;
+use solana_sdk::transaction::Transaction;
+
+/// Returns a nonce account in `state` the way `getAccountInfo` does.
+fn nonce_account(state: State) -> Account {
+    Account::new_data(1_447_680, &Versions::new_current(state), &system_program::id()).unwrap()
+}
+
+/// Accepts a nonce account only with the expected nonce and authority.
+#[test]
+fn check() {
+    let authority = Keypair::new().pubkey();
+    let nonce = Hash::new_unique();
+    let account = nonce_account(State::Initialized(Data::new(authority, nonce, 5_000)));
+
+    assert!(check_nonce_account(&account, &authority, &nonce).is_ok());
+    assert!(matches!(
+        check_nonce_account(&account, &authority, &Hash::new_unique()),
+        Err(Error::InvalidNonce(nonce_utils::Error::InvalidHash))
+    ));
+    assert!(matches!(
+        check_nonce_account(&account, &Pubkey::new_unique(), &nonce),
+        Err(Error::InvalidNonce(nonce_utils::Error::InvalidAuthority))
+    ));
+
+    assert_eq!(nonce_blockhash(&account, None).unwrap(), nonce);
+    assert_eq!(nonce_blockhash(&account, Some(nonce)).unwrap(), nonce);
+    assert!(matches!(
+        nonce_blockhash(&account, Some(Hash::new_unique())),
+        Err(Error::InvalidNonce(nonce_utils::Error::InvalidHash))
+    ));
+
+    let uninitialized = nonce_account(State::Uninitialized);
+    assert!(matches!(check_nonce_account(&uninitialized, &authority, &nonce), Err(Error::InvalidNonce(_))));
+    assert!(matches!(nonce_blockhash(&uninitialized, None), Err(Error::InvalidNonce(_))));
+
+    let not_a_nonce_account = Account {
+        owner: Pubkey::new_unique(),
+        ..account
+    };
+    assert!(matches!(nonce_blockhash(&not_a_nonce_account, None), Err(Error::InvalidNonce(_))));
+}
+
+/// A separate nonce authority signs next to the sender, and signs once when it is the sender.
+#[test]
+fn signers() {
+    let sender = Keypair::new();
+    let authority = Keypair::new();
+    let pubkeys = |signers: &[&Keypair]| signers.iter().map(|signer| signer.pubkey()).collect::<Vec<_>>();
+
+    let (signers, nonce_authority) = transaction_signers(&sender, None);
+    assert_eq!(pubkeys(&signers), [sender.pubkey()]);
+    assert_eq!(nonce_authority.pubkey(), sender.pubkey());
+
+    let sender_again = Keypair::from_bytes(&sender.to_bytes()).unwrap();
+    let (signers, nonce_authority) = transaction_signers(&sender, Some(&sender_again));
+    assert_eq!(pubkeys(&signers), [sender.pubkey()]);
+    assert_eq!(nonce_authority.pubkey(), sender.pubkey());
+
+    let (signers, nonce_authority) = transaction_signers(&sender, Some(&authority));
+    assert_eq!(pubkeys(&signers), [sender.pubkey(), authority.pubkey()]);
+    assert_eq!(nonce_authority.pubkey(), authority.pubkey());
+
+    // Exactly the signers a nonce transaction with a separate authority needs.
+    let nonce = Hash::new_unique();
+    let message = Message::new_with_nonce(
+        vec![system_instruction::transfer(&sender.pubkey(), &Pubkey::new_unique(), 1_000)],
+        Some(&sender.pubkey()),
+        &Pubkey::new_unique(),
+        &authority.pubkey(),
+    );
+    let mut transaction = Transaction::new_unsigned(message);
+    transaction.try_sign(&signers, nonce).unwrap();
+    assert!(transaction.verify().is_ok());
+}
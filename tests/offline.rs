@@ -0,0 +1,57 @@
+use stream_pay_core as core;
+
+use solana_sdk::hash::Hash;
+use solana_sdk::message::Message;
+use solana_sdk::signature::Signer;
+use solana_sdk::signer::keypair::Keypair;
+use solana_sdk::system_instruction;
+
+/// Runs the signing half of the air-gapped flow, which must work without network access.
+#[test]
+fn main() {
+    let sender = Keypair::new();
+    let recipient = Keypair::new().pubkey();
+    let blockhash = Hash::new_unique();
+
+    let message = Message::new_with_blockhash(
+        &[system_instruction::transfer(&sender.pubkey(), &recipient, 1_000)],
+        Some(&sender.pubkey()),
+        &blockhash,
+    );
+    let unsigned = core::offline::encode_message(message).unwrap();
+    assert!(!core::offline::decode_transaction(&unsigned).unwrap().is_signed());
+
+    let wrong_signer = Keypair::new();
+    assert!(core::offline::sign_transaction(&wrong_signer, &unsigned).is_err());
+
+    let signed = core::offline::sign_transaction(&sender, &unsigned).unwrap();
+    let transaction = core::offline::decode_transaction(&signed).unwrap();
+    assert!(transaction.is_signed());
+    assert!(transaction.verify().is_ok());
+    assert_eq!(transaction.message.recent_blockhash, blockhash);
+
+    assert!(core::offline::decode_transaction("not a transaction").is_err());
+}
+
+/// A sponsor pays the fee of a transfer, and both parties sign independently.
+#[test]
+fn sponsored() {
+    let sender = Keypair::new();
+    let sponsor = Keypair::new();
+    let recipient = Keypair::new().pubkey();
+
+    let message = Message::new_with_blockhash(
+        &[system_instruction::transfer(&sender.pubkey(), &recipient, 1_000)],
+        Some(&sponsor.pubkey()),
+        &Hash::new_unique(),
+    );
+    let unsigned = core::offline::encode_message(message).unwrap();
+    assert_eq!(core::offline::missing_signers(&unsigned).unwrap(), vec![sponsor.pubkey(), sender.pubkey()]);
+
+    let signed_by_sender = core::offline::sign_transaction(&sender, &unsigned).unwrap();
+    assert_eq!(core::offline::missing_signers(&signed_by_sender).unwrap(), vec![sponsor.pubkey()]);
+
+    let signed = core::offline::sign_transaction(&sponsor, &signed_by_sender).unwrap();
+    assert!(core::offline::missing_signers(&signed).unwrap().is_empty());
+    assert!(core::offline::decode_transaction(&signed).unwrap().verify().is_ok());
+}
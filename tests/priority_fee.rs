@@ -0,0 +1,51 @@
+use stream_pay_core as core;
+
+use core::compute_budget::{compute_budget_instructions, priority_fee_lamports};
+use core::{Lamports, PriorityFee, SpendAmount, TransactionConfig};
+use solana_sdk::signature::Signer;
+use solana_sdk::signer::keypair::Keypair;
+
+mod test_helpers;
+use test_helpers::{RPC_ENDPOINT, TESTNET_KEY};
+
+/// Checks the encoding of the compute budget instructions and the fee arithmetic offline.
+#[test]
+fn instructions() {
+    let ixs = compute_budget_instructions(200_000, 1_500);
+    assert_eq!(ixs.len(), 2);
+    assert_eq!(ixs[0].program_id, solana_sdk::compute_budget::id());
+    assert_eq!(ixs[0].data, [&[2u8][..], &200_000u32.to_le_bytes()].concat());
+    assert_eq!(ixs[1].data, [&[3u8][..], &1_500u64.to_le_bytes()].concat());
+
+    assert_eq!(priority_fee_lamports(200_000, 1_500), 300);
+    assert_eq!(priority_fee_lamports(1, 1), 1);
+    assert_eq!(priority_fee_lamports(200_000, 0), 0);
+    assert_eq!(priority_fee_lamports(u32::MAX, 1_000_000), u32::MAX as u64);
+    assert_eq!(priority_fee_lamports(u32::MAX, u64::MAX), u64::MAX);
+}
+
+/// A fixed priority fee is added to the base fee of a transfer.
+#[test]
+fn fixed() {
+    let sender = Keypair::from_bytes(&*TESTNET_KEY).unwrap();
+    let sender_pubkey = sender.pubkey();
+    let client = core::PayClient::new(core::Cluster::Custom(RPC_ENDPOINT.to_string()));
+    let amount = SpendAmount::Some(Lamports(1_000));
+
+    let base = client
+        .create_transaction_with_config(&sender_pubkey, amount, &sender_pubkey, &TransactionConfig::default())
+        .unwrap();
+    let config = TransactionConfig {
+        priority_fee: PriorityFee::Fixed {
+            compute_unit_limit: 1_000,
+            micro_lamports: 1_000_000,
+        },
+        ..TransactionConfig::default()
+    };
+    let prioritized = client
+        .create_transaction_with_config(&sender_pubkey, amount, &sender_pubkey, &config)
+        .unwrap();
+
+    assert_eq!(prioritized.message.instructions.len(), base.message.instructions.len() + 2);
+    assert_eq!(prioritized.fee, Lamports(base.fee.lamports() + 1_000));
+}
@@ -0,0 +1,31 @@
+use stream_pay_core as core;
+
+use core::confirmation::{RebroadcastConfig, SendOutcome};
+use solana_sdk::signature::Signer;
+use solana_sdk::signer::keypair::Keypair;
+
+mod test_helpers;
+use test_helpers::{RPC_ENDPOINT, TESTNET_KEY};
+
+/// Sends 0.001 SOL back to the sending address with rebroadcasting and expects it to land.
+#[test]
+fn main() {
+    let sender = Keypair::from_bytes(&*TESTNET_KEY).unwrap();
+    let sender_pubkey = sender.pubkey();
+    let client = core::PayClient::new(core::Cluster::Custom(RPC_ENDPOINT.to_string()));
+
+    let prepared = client.create_transaction(&sender_pubkey, core::Lamports(1_000_000), &sender_pubkey).expect("Failed to prepare transaction");
+    assert!(!prepared.is_expired(&client).unwrap());
+    let config = RebroadcastConfig {
+        last_valid_block_height: prepared.last_valid_block_height,
+        ..RebroadcastConfig::default()
+    };
+
+    let outcome = client
+        .finish_transaction_with_rebroadcast(&sender, prepared.message, &config)
+        .expect("Failed to send transaction");
+    match outcome {
+        SendOutcome::Confirmed(confirmation) => assert!(confirmation.status.is_ok()),
+        SendOutcome::Expired(signature) => panic!("Transaction {} expired", signature),
+    }
+}
@@ -0,0 +1,61 @@
+use stream_pay_core as core;
+
+use core::recipient::{analyze_recipient_account, RecipientWarning, Severity};
+use solana_sdk::account::Account;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signer;
+use solana_sdk::signer::keypair::Keypair;
+
+/// Classifies recipients from their account data, without network access.
+#[test]
+fn main() {
+    let sender = Keypair::new().pubkey();
+    let wallet = Keypair::new().pubkey();
+    let token_program = core::token::token_program::id();
+
+    assert!(analyze_recipient_account(&sender, &wallet, None).is_empty());
+    assert_eq!(analyze_recipient_account(&sender, &sender, None), vec![RecipientWarning::SelfTransfer]);
+    assert_eq!(
+        analyze_recipient_account(&sender, &solana_sdk::incinerator::id(), None),
+        vec![RecipientWarning::BurnAddress]
+    );
+
+    let (pda, _) = Pubkey::find_program_address(&[b"vault"], &token_program);
+    assert_eq!(analyze_recipient_account(&sender, &pda, None), vec![RecipientWarning::OffCurve]);
+    assert_eq!(RecipientWarning::OffCurve.severity(), Severity::Warning);
+
+    let program = Account {
+        executable: true,
+        ..Account::default()
+    };
+    assert_eq!(analyze_recipient_account(&sender, &wallet, Some(&program)), vec![RecipientWarning::Executable]);
+
+    let mint = Account {
+        owner: token_program,
+        data: vec![0; 82],
+        ..Account::default()
+    };
+    assert_eq!(analyze_recipient_account(&sender, &wallet, Some(&mint)), vec![RecipientWarning::TokenMint]);
+
+    let mint_address = Keypair::new().pubkey();
+    let mut data = vec![0; 165];
+    data[..32].copy_from_slice(mint_address.as_ref());
+    data[32..64].copy_from_slice(wallet.as_ref());
+    let token_account = Account {
+        owner: token_program,
+        data,
+        ..Account::default()
+    };
+    let warnings = analyze_recipient_account(&sender, &sender, Some(&token_account));
+    assert_eq!(
+        warnings,
+        vec![
+            RecipientWarning::TokenAccount {
+                mint: mint_address,
+                owner: wallet,
+            },
+            RecipientWarning::SelfTransfer,
+        ]
+    );
+    assert_eq!(warnings[0].severity(), Severity::Blocking);
+}
@@ -0,0 +1,37 @@
+use stream_pay_core as core;
+
+use solana_sdk::message::Message;
+use solana_sdk::signature::Signer;
+use solana_sdk::signer::keypair::Keypair;
+use solana_sdk::system_instruction;
+
+mod test_helpers;
+use test_helpers::{RPC_ENDPOINT, TESTNET_KEY};
+
+/// Simulates a transfer to a new account: enough for rent exemption succeeds, 1 lamport fails.
+#[test]
+fn main() {
+    let sender = Keypair::from_bytes(&*TESTNET_KEY).unwrap();
+    let sender_pubkey = sender.pubkey();
+    let recipient = Keypair::new().pubkey();
+    let client = core::PayClient::new(core::Cluster::Custom(RPC_ENDPOINT.to_string()));
+
+    let prepared = client.create_transaction(&sender_pubkey, core::Lamports(10_000_000), &recipient).expect("Failed to prepare transaction");
+    let simulation = client.simulate_transaction(&prepared.message).unwrap();
+    assert_eq!(simulation.error, None);
+    assert!(!simulation.logs.is_empty());
+    let received = simulation
+        .balance_changes
+        .iter()
+        .find(|change| change.address == recipient)
+        .unwrap();
+    assert_eq!(received.delta(), 10_000_000);
+
+    let result = client.create_transaction_with_config(&sender_pubkey, core::SpendAmount::Some(core::Lamports(1)), &recipient, &core::TransactionConfig::default());
+    assert!(matches!(result, Err(core::Error::RecipientNotRentExempt(core::Lamports(1), _, address)) if address == recipient));
+
+    let message = Message::new(&[system_instruction::transfer(&sender_pubkey, &recipient, 1)], Some(&sender_pubkey));
+    let simulation = client.simulate_transaction(&message).unwrap();
+    assert!(simulation.error.is_some());
+    assert!(simulation.balance_changes.iter().all(|change| change.delta() == 0));
+}
@@ -0,0 +1,59 @@
+use stream_pay_core as core;
+
+use core::{check_remaining_balance, Error, Lamports, SpendAmount};
+use solana_sdk::signature::Signer;
+use solana_sdk::signer::keypair::Keypair;
+
+const FEE: Lamports = Lamports(5_000);
+const RENT_EXEMPT_MINIMUM: Lamports = Lamports(890_880);
+
+/// Resolves the transferred amount for each variant when the sender pays the fee.
+#[test]
+fn sender_pays_fee() {
+    let sender = Keypair::new().pubkey();
+    let resolve = |amount: SpendAmount, balance| amount.resolve(Lamports(balance), FEE, RENT_EXEMPT_MINIMUM, &sender, &sender);
+
+    assert_eq!(resolve(SpendAmount::Some(Lamports(7)), 0).unwrap(), Lamports(7));
+    assert_eq!(resolve(SpendAmount::All, 10_000).unwrap(), Lamports(5_000));
+    assert_eq!(resolve(SpendAmount::AllExcept(Lamports(1_000)), 10_000).unwrap(), Lamports(4_000));
+    assert_eq!(resolve(SpendAmount::AllExceptRentExempt, 1_000_000).unwrap(), Lamports(104_120));
+    assert_eq!(resolve(SpendAmount::AllExceptRentExempt, 895_881).unwrap(), Lamports(1));
+
+    assert!(matches!(resolve(SpendAmount::All, 5_000), Err(Error::NothingToSend(Lamports(5_000), FEE, address)) if address == sender));
+    assert!(matches!(resolve(SpendAmount::All, 0), Err(Error::NothingToSend(Lamports(0), FEE, _))));
+    assert!(matches!(resolve(SpendAmount::AllExcept(Lamports(5_000)), 10_000), Err(Error::NothingToSend(_, Lamports(10_000), _))));
+    assert!(matches!(resolve(SpendAmount::AllExcept(Lamports(20_000)), 10_000), Err(Error::NothingToSend(_, Lamports(25_000), _))));
+    assert!(matches!(resolve(SpendAmount::AllExcept(Lamports(u64::MAX)), 10_000), Err(Error::NothingToSend(_, Lamports(u64::MAX), _))));
+    assert!(matches!(resolve(SpendAmount::AllExceptRentExempt, 895_880), Err(Error::NothingToSend(_, Lamports(895_880), _))));
+}
+
+/// The fee isn't taken from the sender's balance when another account pays it.
+#[test]
+fn separate_fee_payer() {
+    let sender = Keypair::new().pubkey();
+    let fee_payer = Keypair::new().pubkey();
+    let resolve = |amount: SpendAmount, balance| amount.resolve(Lamports(balance), FEE, RENT_EXEMPT_MINIMUM, &sender, &fee_payer);
+
+    assert_eq!(resolve(SpendAmount::All, 10_000).unwrap(), Lamports(10_000));
+    assert_eq!(resolve(SpendAmount::AllExcept(Lamports(1_000)), 10_000).unwrap(), Lamports(9_000));
+    assert!(matches!(resolve(SpendAmount::All, 0), Err(Error::NothingToSend(Lamports(0), Lamports(0), address)) if address == sender));
+    assert!(matches!(resolve(SpendAmount::AllExceptRentExempt, 890_880), Err(Error::NothingToSend(_, RENT_EXEMPT_MINIMUM, _))));
+}
+
+/// Accounts paying for a transfer must end up empty or rent exempt, whether they pay the spend, the
+/// fee or both.
+#[test]
+fn remaining_balance() {
+    let account = Keypair::new().pubkey();
+    let check = |balance, cost| check_remaining_balance(&account, Lamports(balance), Lamports(cost), RENT_EXEMPT_MINIMUM);
+
+    assert!(check(1_000_000, 1_000_000).is_ok());
+    assert!(check(0, 0).is_ok());
+    assert!(check(895_880, 5_000).is_ok());
+    assert!(check(1_000_000, 109_120).is_ok());
+    assert!(matches!(check(1_000_000, 109_121), Err(Error::SenderNotRentExempt(Lamports(890_879), _, address)) if address == account));
+    assert!(matches!(check(1_000_000, 999_999), Err(Error::SenderNotRentExempt(Lamports(1), ..))));
+    assert!(matches!(check(890_879, 0), Err(Error::SenderNotRentExempt(..))));
+    // A self-transfer only costs the fee, which must not strand the rest.
+    assert!(matches!(check(10_000, 5_000), Err(Error::SenderNotRentExempt(Lamports(5_000), ..))));
+}
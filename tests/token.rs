@@ -0,0 +1,155 @@
+use stream_pay_core as core;
+
+use core::token::{
+    associated_token_account_len, associated_token_program, create_associated_token_account_idempotent,
+    get_associated_token_address, parse_token_balance, token_2022_program, token_program, transfer_checked,
+    transfer_hook_program,
+};
+use core::TokenAmount;
+use solana_client::rpc_response::RpcKeyedAccount;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signer;
+use solana_sdk::signer::keypair::Keypair;
+use std::str::FromStr;
+
+const USDC_MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+/// Derives the USDC account of the first Phantom address of the BIP39 test mnemonic.
+#[test]
+fn associated_token_address() {
+    let owner = Pubkey::from_str("HAgk14JpMQLgt6rVgv7cBQFJWFto5Dqxi472uT3DKpqk").unwrap();
+    let mint = Pubkey::from_str(USDC_MINT).unwrap();
+
+    assert_eq!(
+        get_associated_token_address(&owner, &mint, &token_program::id()).to_string(),
+        "5N3f1tj9v1vc5TUZ8S7mCAnVmjVKrfnzXWhxLaxyZAgt"
+    );
+    assert_ne!(
+        get_associated_token_address(&owner, &mint, &token_2022_program::id()),
+        get_associated_token_address(&owner, &mint, &token_program::id())
+    );
+}
+
+/// Encodes instructions the way the `spl-token` and `spl-associated-token-account` crates do.
+#[test]
+fn instructions() {
+    let sender = Keypair::new().pubkey();
+    let recipient = Keypair::new().pubkey();
+    let mint = Pubkey::from_str(USDC_MINT).unwrap();
+    let source = get_associated_token_address(&sender, &mint, &token_program::id());
+    let destination = get_associated_token_address(&recipient, &mint, &token_program::id());
+
+    let ix = transfer_checked(&token_program::id(), &source, &mint, &destination, &sender, 1_500_000, 6);
+    assert_eq!(ix.program_id, token_program::id());
+    assert_eq!(ix.data, [&[12][..], &1_500_000u64.to_le_bytes(), &[6]].concat());
+    let accounts = ix.accounts.iter().map(|meta| (meta.pubkey, meta.is_writable, meta.is_signer)).collect::<Vec<_>>();
+    assert_eq!(
+        accounts,
+        [(source, true, false), (mint, false, false), (destination, true, false), (sender, false, true)]
+    );
+
+    let ix = create_associated_token_account_idempotent(&sender, &recipient, &mint, &token_2022_program::id());
+    assert_eq!(ix.program_id, associated_token_program::id());
+    assert_eq!(ix.data, [1]);
+    assert_eq!(ix.accounts[0].pubkey, sender);
+    assert!(ix.accounts[0].is_signer && ix.accounts[0].is_writable);
+    assert_eq!(ix.accounts[1].pubkey, get_associated_token_address(&recipient, &mint, &token_2022_program::id()));
+    assert_eq!(ix.accounts[2].pubkey, recipient);
+    assert_eq!(ix.accounts[5].pubkey, token_2022_program::id());
+}
+
+/// Builds Token-2022 mint data with the given `(type, length)` extensions.
+fn mint_2022(extensions: &[(u16, u16)]) -> Vec<u8> {
+    let mut data = vec![0; 165];
+    data.push(1);
+    for (extension_type, len) in extensions {
+        data.extend_from_slice(&extension_type.to_le_bytes());
+        data.extend_from_slice(&len.to_le_bytes());
+        data.resize(data.len() + *len as usize, 0);
+    }
+    data
+}
+
+/// Sizes new associated token accounts from the mint's extensions.
+#[test]
+fn account_len() {
+    assert_eq!(associated_token_account_len(&[0; 82], &token_program::id()), Some(165));
+    assert_eq!(associated_token_account_len(&[0; 82], &token_2022_program::id()), Some(170));
+
+    let len = |extensions| associated_token_account_len(&mint_2022(extensions), &token_2022_program::id());
+    // Metadata pointer.
+    assert_eq!(len(&[(18, 64)]), Some(170));
+    // Transfer fee config, with a 4 byte header and 8 bytes of `TransferFeeAmount` on accounts.
+    assert_eq!(len(&[(1, 108)]), Some(182));
+    // Transfer fee config and transfer hook.
+    assert_eq!(len(&[(1, 108), (14, 64)]), Some(187));
+    assert_eq!(len(&[(1, 108), (1000, 8)]), None);
+}
+
+/// Finds the hook program of Token-2022 mints, which transfers can't be built for.
+#[test]
+fn transfer_hook() {
+    let hook_program_id = Keypair::new().pubkey();
+    let mut data = mint_2022(&[(1, 108), (14, 64)]);
+    let program_id_offset = data.len() - 32;
+    data[program_id_offset..].copy_from_slice(hook_program_id.as_ref());
+
+    assert_eq!(transfer_hook_program(&data, &token_2022_program::id()), Some(hook_program_id));
+    assert_eq!(transfer_hook_program(&data, &token_program::id()), None);
+    // A hook without a program id invokes nothing.
+    assert_eq!(transfer_hook_program(&mint_2022(&[(14, 64)]), &token_2022_program::id()), None);
+    assert_eq!(transfer_hook_program(&mint_2022(&[(1, 108)]), &token_2022_program::id()), None);
+}
+
+/// Returns a token account the way `getTokenAccountsByOwner` does with `jsonParsed` encoding.
+fn keyed_account(address: &Pubkey, info: serde_json::Value) -> RpcKeyedAccount {
+    serde_json::from_value(serde_json::json!({
+        "pubkey": address.to_string(),
+        "account": {
+            "lamports": 2_039_280,
+            "owner": token_program::id().to_string(),
+            "executable": false,
+            "rentEpoch": 0,
+            "data": {
+                "program": "spl-token",
+                "parsed": { "type": "account", "info": info },
+                "space": 165,
+            },
+        },
+    }))
+    .unwrap()
+}
+
+/// Decodes parsed token accounts, and reports those in an unexpected shape.
+#[test]
+fn balances() {
+    let address = Keypair::new().pubkey();
+    let owner = Keypair::new().pubkey();
+    let info = |state: &str, amount: &str| {
+        serde_json::json!({
+            "mint": USDC_MINT,
+            "owner": owner.to_string(),
+            "tokenAmount": { "amount": amount, "decimals": 6, "uiAmount": null, "uiAmountString": "" },
+            "state": state,
+            "isNative": false,
+        })
+    };
+
+    let balance = parse_token_balance(&keyed_account(&address, info("initialized", "1500000")), token_program::id()).unwrap();
+    assert_eq!(balance.address, address);
+    assert_eq!(balance.mint, Pubkey::from_str(USDC_MINT).unwrap());
+    assert_eq!(balance.program_id, token_program::id());
+    assert_eq!(balance.amount, TokenAmount::new(1_500_000, 6));
+    assert_eq!(balance.ui_amount(), "1.5");
+    assert!(!balance.is_frozen);
+
+    let balance = parse_token_balance(&keyed_account(&address, info("frozen", "0")), token_program::id()).unwrap();
+    assert!(balance.is_frozen);
+
+    for unexpected in [info("initialized", "1.5"), serde_json::json!({ "mint": USDC_MINT })] {
+        assert!(matches!(
+            parse_token_balance(&keyed_account(&address, unexpected), token_program::id()),
+            Err(core::Error::RpcRequestError(_))
+        ));
+    }
+}
@@ -9,10 +9,9 @@ mod test_helpers;
 use test_helpers::{RPC_ENDPOINT, TESTNET_KEY};
 use std::iter::zip;
 
-fn send_transaction(sender: &Keypair, receiver: &Pubkey, amount: f64) {
-    let initial_balance = core::get_balance(RPC_ENDPOINT, &sender.pubkey().to_string()).unwrap();
-    let core::PreparedTransaction { message, fee: _ } = core::create_transaction(
-        RPC_ENDPOINT,
+fn send_transaction(client: &core::PayClient, sender: &Keypair, receiver: &Pubkey, amount: core::Lamports) {
+    let initial_balance = client.get_balance(&sender.pubkey().to_string()).unwrap();
+    let core::PreparedTransaction { message, .. } = client.create_transaction(
         &sender.pubkey(), 
         amount, 
         &receiver).expect("Failed to prepare transaction");
@@ -23,7 +22,7 @@ fn send_transaction(sender: &Keypair, receiver: &Pubkey, amount: f64) {
     loop {
         std::thread::sleep(std::time::Duration::from_secs(5));
 
-        let new_balance = core::get_balance(RPC_ENDPOINT, &sender.pubkey().to_string()).unwrap();
+        let new_balance = client.get_balance(&sender.pubkey().to_string()).unwrap();
         if new_balance != initial_balance {
             break new_balance;
         }
@@ -42,13 +41,14 @@ fn main() {
     let sender = Keypair::from_bytes(&*TESTNET_KEY).unwrap();
     let sender_pubkey = sender.pubkey();
     let random_recipient = Keypair::new().pubkey();
-    let initial_balance = core::get_balance(RPC_ENDPOINT, &sender_pubkey.to_string()).unwrap();
-    let min_sol_for_test = 0.05;
-    assert!(initial_balance > min_sol_for_test, "Test requires more sol to run. please run `solana --url=testnet --keypair=testnet_key.json airdrop {:?}` prior to running test", min_sol_for_test);
-    let mut amounts = vec![0.01, 0.02];
+    let client = core::PayClient::new(core::Cluster::Custom(RPC_ENDPOINT.to_string()));
+    let initial_balance = client.get_balance(&sender_pubkey.to_string()).unwrap();
+    let min_sol_for_test: core::Lamports = "0.05".parse().unwrap();
+    assert!(initial_balance > min_sol_for_test, "Test requires more sol to run. please run `solana --url=testnet --keypair=testnet_key.json airdrop {}` prior to running test", min_sol_for_test);
+    let mut amounts: Vec<core::Lamports> = vec!["0.01".parse().unwrap(), "0.02".parse().unwrap()];
     let limit = (&amounts.len()).clone();
     for amount in &amounts { 
-        send_transaction(&sender, &random_recipient, *amount);
+        send_transaction(&client, &sender, &random_recipient, *amount);
     }
 
     let rpc_client = RpcClient::new(RPC_ENDPOINT.to_string());
@@ -63,6 +63,17 @@ fn main() {
     }).collect::<Vec<_>>();
     assert_eq!(history.len(), limit);
     amounts.reverse();
+
+    let records = client.get_payment_history(&sender_pubkey, None, None, limit).unwrap();
+    for (record, amount) in zip(&records, &amounts) {
+        assert_eq!(record.direction, core::history::Direction::Outgoing);
+        assert_eq!(record.asset, core::history::Asset::Sol);
+        assert_eq!(record.counterparty, Some(random_recipient));
+        assert_eq!(record.amount, core::TokenAmount::from(*amount));
+        assert_eq!(record.fee_payer, sender_pubkey);
+        assert!(record.status.is_ok());
+    }
+
     for (transaction_with_data, amount) in zip(history.clone(), amounts) {
         let transaction = transaction_with_data.transaction.decode().unwrap();
         assert!(transaction.message.account_keys.len() >= 2);
@@ -81,7 +92,7 @@ fn main() {
             receiver_post_amount-receiver_pre_amount,
             &metadata.fee
         );
-        assert_eq!(receiver_post_amount-receiver_pre_amount, core::sol_to_lamports(amount));
-        assert_eq!(sender_pre_amount-sender_post_amount - &metadata.fee, core::sol_to_lamports(amount));
+        assert_eq!(receiver_post_amount-receiver_pre_amount, amount.lamports());
+        assert_eq!(sender_pre_amount-sender_post_amount - &metadata.fee, amount.lamports());
     }
 }